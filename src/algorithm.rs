@@ -0,0 +1,44 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+/// A rate limiting algorithm driven by a [Quota], so limiters built on top of it
+/// can swap one algorithm for another, e.g. for comparison or migration.
+pub trait RateLimitAlgorithm {
+    /// Check if we are allowed to proceed at the given arrival time, and consume `cost`
+    /// resources if so.
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error>;
+
+    /// Gives back `cost` resources consumed by a previous check.
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32);
+
+    /// Amount of resources that can still be consumed at `now`.
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32;
+}
+
+impl RateLimitAlgorithm for State {
+    #[inline]
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        State::check_and_modify_at(self, rate_limit, arrived_at, cost)
+    }
+
+    #[inline]
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        State::revert_at(self, rate_limit, arrived_at, cost)
+    }
+
+    #[inline]
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        State::remaining_resources(self, rate_limit, now)
+    }
+}
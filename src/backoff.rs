@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+/// Bridges limiter denials into a backoff policy: wraps a sequence of retry delays, as
+/// taken by most retry crates, so no delay ends before the limiter allows a retry.
+///
+/// ```
+/// use std::time::Duration;
+/// use gcra::{Backoff, Quota, State};
+///
+/// let quota = Quota::new(1, Duration::from_secs(1));
+/// let mut state = State::default();
+/// let mut backoff = Backoff::new((1..).map(Duration::from_millis));
+///
+/// state.check_and_modify(&quota, 1).unwrap();
+/// let err = state.check_and_modify(&quota, 1).unwrap_err();
+/// assert!(backoff.observe(&err), "the denial is temporary");
+///
+/// let delay = backoff.next().unwrap();
+/// assert!(delay > Duration::from_millis(900), "waits for the limiter, not 1ms");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Backoff<I> {
+    inner: I,
+    not_before: Option<Instant>,
+}
+
+impl<I: Iterator<Item = Duration>> Backoff<I> {
+    pub fn new(inner: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            inner: inner.into_iter(),
+            not_before: None,
+        }
+    }
+
+    /// Records a denial, so the next delay lasts at least until the limiter allows a retry.
+    ///
+    /// # Returns
+    /// False if the error isn't a temporary denial, and retrying is pointless.
+    pub fn observe(&mut self, err: &Error) -> bool {
+        match err {
+            Error::DeniedUntil(next) => {
+                self.not_before = Some(self.not_before.map_or(*next, |prev| prev.max(*next)));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The next delay, as of `now`, or `None` once the wrapped policy gives up.
+    pub fn next_at(&mut self, now: Instant) -> Option<Duration> {
+        let delay = self.inner.next()?;
+
+        match self.not_before.take() {
+            Some(not_before) => Some(delay.max(not_before.saturating_duration_since(now))),
+            None => Some(delay),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Backoff<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.next_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_honors_limiter() {
+        let now = Instant::now();
+        let mut backoff = Backoff::new([Duration::from_millis(100), Duration::from_secs(2)]);
+
+        assert!(backoff.observe(&Error::DeniedUntil(now + Duration::from_secs(1))));
+        assert_eq!(Some(Duration::from_secs(1)), backoff.next_at(now));
+        assert!(backoff.observe(&Error::DeniedUntil(now + Duration::from_secs(1))));
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            backoff.next_at(now),
+            "longer delays of the policy are kept"
+        );
+        assert_eq!(None, backoff.next_at(now));
+    }
+
+    #[test]
+    fn backoff_gives_up_on_indefinite_denial() {
+        let mut backoff = Backoff::new([Duration::from_millis(100)]);
+        assert!(!backoff.observe(&Error::DeniedIndefinitely(10)));
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Quota, State};
+
+/// Limits throughput in bytes, where the quota's `resource_limit` is the amount of bytes
+/// per `period`, for proxies and file transfers.
+///
+/// Transfers larger than the burst are split into chunks of at most the burst, booked one
+/// after the other, so they are paced rather than denied.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    quota: Quota,
+    state: Mutex<State>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Books `bytes` as of `now`.
+    ///
+    /// # Returns
+    /// When the last chunk is allowed to be sent, `now` if right away. Only
+    /// [Error::DeniedIndefinitely] for a zero quota, or [Error::TimeOverflow], in which
+    /// case nothing is booked.
+    pub fn reserve_bytes_at(&self, now: Instant, bytes: u64) -> Result<Instant, Error> {
+        if self.quota.is_zero() && bytes > 0 {
+            return Err(Error::DeniedIndefinitely(bytes.min(u32::MAX as u64) as u32));
+        }
+
+        if bytes == 0 || self.quota.is_unlimited() {
+            return Ok(now);
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let mut next = state.clone();
+
+        // Every chunk but the last is a full burst, booked back to back, so they only
+        // move the TAT forward, and the last one is allowed the latest
+        let burst = self.quota.burst as u64;
+        let full_chunks = (bytes - 1) / burst;
+        let last_chunk = (bytes - full_chunks * burst) as u32;
+        if full_chunks > 0 {
+            let nanos = self
+                .quota
+                .increment_interval(self.quota.burst)
+                .as_nanos()
+                .checked_mul(full_chunks as u128)
+                .ok_or(Error::TimeOverflow)?;
+            let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| Error::TimeOverflow)?;
+            let full = Duration::new(secs, (nanos % 1_000_000_000) as u32);
+
+            let tat = next.tat.map_or(now, |tat| tat.max(now));
+            next.tat = Some(tat.checked_add(full).ok_or(Error::TimeOverflow)?);
+        }
+
+        let at = match next.check_and_modify_at(&self.quota, now, last_chunk) {
+            Ok(()) => now,
+            Err(Error::DeniedUntil(at)) => {
+                next.check_and_modify_at(&self.quota, at, last_chunk)?;
+                at
+            }
+            Err(err) => return Err(err),
+        };
+
+        *state = next;
+        Ok(at)
+    }
+
+    /// Books `bytes` and blocks the current thread until the last chunk is allowed to
+    /// be sent.
+    pub fn acquire_bytes(&self, bytes: u64) -> Result<(), Error> {
+        let now = Instant::now();
+        let until = self.reserve_bytes_at(now, bytes)?;
+        std::thread::sleep(until.saturating_duration_since(Instant::now()));
+        Ok(())
+    }
+
+    /// Bytes which can be transferred right away at `now`.
+    pub fn available_at(&self, now: Instant) -> u32 {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remaining_resources(&self.quota, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bandwidth_chunks_large_transfers() {
+        // 1 kB/s with a burst of 250 bytes
+        let limiter =
+            BandwidthLimiter::new(Quota::new(1000, Duration::from_secs(1)).with_burst(250));
+        let now = Instant::now();
+
+        assert_eq!(now, limiter.reserve_bytes_at(now, 250).unwrap());
+        assert_eq!(0, limiter.available_at(now));
+
+        // the last of 8 more chunks is sent 2 seconds later
+        assert_eq!(
+            now + Duration::from_secs(2),
+            limiter.reserve_bytes_at(now, 2000).unwrap()
+        );
+    }
+
+    #[test]
+    fn bandwidth_huge_transfer() {
+        let limiter = BandwidthLimiter::new(Quota::new(1000, Duration::from_secs(1)));
+        let now = Instant::now();
+
+        // 2500 bytes are 2 full chunks and half a chunk
+        assert_eq!(
+            now + Duration::from_millis(1500),
+            limiter.reserve_bytes_at(now, 2500).unwrap()
+        );
+
+        // paced for ages rather than looping over every chunk
+        let until = limiter.reserve_bytes_at(now, u64::MAX).unwrap();
+        assert!(until > now + Duration::from_secs(u64::MAX / 1000 - 1));
+        assert_eq!(0, limiter.available_at(now));
+    }
+
+    #[test]
+    fn bandwidth_zero_quota() {
+        let limiter = BandwidthLimiter::new(Quota::zero());
+        let now = Instant::now();
+
+        assert!(matches!(
+            limiter.reserve_bytes_at(now, u64::MAX),
+            Err(Error::DeniedIndefinitely(u32::MAX))
+        ));
+        assert_eq!(now, limiter.reserve_bytes_at(now, 0).unwrap());
+    }
+}
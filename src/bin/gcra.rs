@@ -0,0 +1,149 @@
+//! Simulates a quota against arrivals, to sanity-check it before deploying it.
+//!
+//! ```text
+//! gcra <limit>/<period> [--burst N] [--cost N] [--every PERIOD --for PERIOD] [--quiet]
+//! ```
+//!
+//! Without `--every`, arrivals are read from stdin as one offset per line, e.g. `1s250ms`.
+
+use std::io::BufRead;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use gcra::sim::{constant, simulate};
+use gcra::{try_parse_period, Error, Quota, State};
+
+/// Longest offset the simulation's clock can track.
+const MAX_OFFSET: Duration = Duration::from_nanos(u64::MAX);
+
+const USAGE: &str =
+    "usage: gcra <limit>/<period> [--burst N] [--cost N] [--every PERIOD --for PERIOD] [--quiet]";
+
+struct Args {
+    quota: Quota,
+    cost: u32,
+    every: Option<Duration>,
+    duration: Option<Duration>,
+    quiet: bool,
+}
+
+fn main() {
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|err| fail(&err));
+    let arrivals = match (args.every, args.duration) {
+        (Some(every), Some(duration)) => constant(every, duration),
+        (None, None) => read_arrivals().unwrap_or_else(|err| fail(&err)),
+        _ => fail("--every and --for go together"),
+    };
+
+    if !args.quiet {
+        let epoch = Instant::now();
+        let mut state = State::default();
+        for offset in &arrivals {
+            let now = epoch
+                .checked_add(*offset)
+                .unwrap_or_else(|| fail(&format!("arrival {:?} is too late", offset)));
+            match state.check_and_modify_at(&args.quota, now, args.cost) {
+                Ok(()) => println!("{:?}\tallowed", offset),
+                Err(Error::DeniedUntil(next)) => {
+                    println!("{:?}\tdenied until {:?}", offset, next - epoch)
+                }
+                Err(err) => println!("{:?}\tdenied: {}", offset, err),
+            }
+        }
+        println!();
+    }
+
+    let report = simulate::<State>(&args.quota, arrivals, args.cost);
+    println!("quota:          {}", args.quota);
+    println!("arrivals:       {}", report.arrivals);
+    println!("admitted:       {}", report.admitted);
+    println!("denied:         {}", report.denied);
+    println!("max burst:      {}", report.max_burst);
+    println!("max per period: {}", report.max_per_period);
+    match report.long_run_rate() {
+        Some(rate) => println!("long run rate:  {:.3}/s", rate),
+        None => println!("long run rate:  n/a"),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let spec = args.next().ok_or("missing quota")?;
+    let (limit, period) = spec
+        .split_once('/')
+        .ok_or("quota must be <limit>/<period>")?;
+    let limit = parse_number(limit)?;
+    let period = period_arg(period)?;
+    if limit == 0 {
+        return Err("limit must be greater than zero".into());
+    }
+
+    let mut args_out = Args {
+        quota: Quota::new(limit, period),
+        cost: 1,
+        every: None,
+        duration: None,
+        quiet: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value of {}", arg));
+        match arg.as_str() {
+            "--burst" => match parse_number(&value()?)? {
+                0 => return Err("burst must be greater than zero".into()),
+                burst => args_out.quota = args_out.quota.with_burst(burst),
+            },
+            "--cost" => args_out.cost = parse_number(&value()?)?,
+            "--every" => args_out.every = Some(period_arg(&value()?)?),
+            "--for" => match period_arg(&value()?)? {
+                duration if duration > MAX_OFFSET => return Err("--for is too long".into()),
+                duration => args_out.duration = Some(duration),
+            },
+            "--quiet" => args_out.quiet = true,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(args_out)
+}
+
+fn parse_number(value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid number {}", value))
+}
+
+fn period_arg(value: &str) -> Result<Duration, String> {
+    match try_parse_period(value.trim()) {
+        Some(period) if period.is_zero() => Err("periods must be greater than zero".into()),
+        Some(period) => Ok(period),
+        None => Err(format!("invalid period {}", value)),
+    }
+}
+
+/// Offsets from stdin, zero offsets included, so arrivals can start right away.
+fn read_arrivals() -> Result<Vec<Duration>, String> {
+    let mut arrivals = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let offset = try_parse_period(line).ok_or(format!("invalid arrival {}", line))?;
+        if offset > MAX_OFFSET {
+            return Err(format!("arrival {} is too late", line));
+        }
+        if arrivals.last().is_some_and(|last| offset < *last) {
+            return Err(format!("arrival {} is before the previous one", line));
+        }
+        arrivals.push(offset);
+    }
+
+    Ok(arrivals)
+}
+
+fn fail(err: &str) -> ! {
+    eprintln!("gcra: {}\n{}", err, USAGE);
+    exit(2)
+}
@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use crate::{Error, Quota, State};
+
+/// Position of a [CircuitBreaker].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    /// Checks go through to the limiter, counting consecutive failures.
+    Closed(u32),
+
+    /// Every check is denied until the [Instant], or forever when `open_for` runs past
+    /// what an [Instant] can represent.
+    Open(Option<Instant>),
+
+    /// A single probe is let through to decide whether to close or re-open,
+    /// and considered lost after the [Instant], if any.
+    HalfOpen(Option<Instant>),
+}
+
+/// Opens a circuit, denying every check, after `failure_threshold` consecutive failures.
+///
+/// Both denials by the limiter and failures reported with
+/// [`record_failure_at()`](CircuitBreaker::record_failure_at) count. Once `open_for` has
+/// passed, a single probe is let through: reporting its success closes the circuit again,
+/// while a failure re-opens it.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    circuit: Circuit,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_for,
+            circuit: Circuit::Closed(0),
+        }
+    }
+
+    /// Position of the circuit at `now`.
+    pub fn circuit(&self, now: Instant) -> Circuit {
+        match self.circuit {
+            Circuit::Open(Some(until)) if until <= now => Circuit::HalfOpen(None),
+            circuit => circuit,
+        }
+    }
+
+    /// Checks `state` if the circuit allows it, counting a denial as a failure, while an
+    /// allowed check resets the failures of a closed circuit.
+    ///
+    /// # Returns
+    /// While the circuit is open, or a probe is pending, [Error::DeniedUntil] the time
+    /// another check may go through, or [Error::DeniedIndefinitely] if it stays open.
+    pub fn check_at(
+        &mut self,
+        state: &mut State,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        match self.circuit(arrived_at) {
+            Circuit::Open(Some(until)) => return Err(Error::DeniedUntil(until)),
+            Circuit::Open(None) => return Err(Error::DeniedIndefinitely(cost)),
+            Circuit::HalfOpen(Some(lost_at)) if arrived_at < lost_at => {
+                return Err(Error::DeniedUntil(lost_at))
+            }
+            Circuit::HalfOpen(_) => match arrived_at.checked_add(self.open_for) {
+                Some(lost_at) => self.circuit = Circuit::HalfOpen(Some(lost_at)),
+                None => {
+                    self.circuit = Circuit::Open(None);
+                    return Err(Error::DeniedIndefinitely(cost));
+                }
+            },
+            Circuit::Closed(_) => {}
+        }
+
+        let result = state.check_and_modify_at(rate_limit, arrived_at, cost);
+        match (&result, self.circuit) {
+            (Err(_), _) => self.record_failure_at(arrived_at),
+            (Ok(()), Circuit::Closed(_)) => self.circuit = Circuit::Closed(0),
+            (Ok(()), _) => {}
+        }
+        result
+    }
+
+    /// Reports a successful operation, closing a half-open circuit.
+    pub fn record_success(&mut self) {
+        match self.circuit {
+            Circuit::Closed(_) | Circuit::HalfOpen(_) => self.circuit = Circuit::Closed(0),
+            Circuit::Open(_) => {}
+        }
+    }
+
+    /// Reports a failed operation, opening the circuit past the threshold, or re-opening
+    /// a half-open one.
+    pub fn record_failure_at(&mut self, now: Instant) {
+        self.circuit = match self.circuit(now) {
+            Circuit::Closed(failures) if failures + 1 < self.failure_threshold => {
+                Circuit::Closed(failures + 1)
+            }
+            Circuit::Open(until) => Circuit::Open(until),
+            _ => Circuit::Open(now.checked_add(self.open_for)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_sustained_denials() {
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let quota = Quota::new(1, Duration::from_secs(1));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        assert!(breaker.check_at(&mut state, &quota, now, 1).is_ok());
+        assert!(breaker.check_at(&mut state, &quota, now, 1).is_err());
+        assert_eq!(Circuit::Closed(1), breaker.circuit(now));
+        assert!(breaker.check_at(&mut state, &quota, now, 1).is_err());
+
+        let reopen_at = now + Duration::from_secs(10);
+        assert_eq!(Circuit::Open(Some(reopen_at)), breaker.circuit(now));
+        assert!(
+            matches!(
+                breaker.check_at(&mut state, &quota, now + Duration::from_secs(5), 1),
+                Err(Error::DeniedUntil(next)) if next == reopen_at
+            ),
+            "open circuit denies even though the quota allows it"
+        );
+    }
+
+    #[test]
+    fn allowed_checks_reset_failures() {
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let quota = Quota::new(1, Duration::from_secs(1));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        // denials which aren't back to back never open the circuit
+        for i in 0..4 {
+            let at = now + Duration::from_secs(i);
+            assert!(breaker.check_at(&mut state, &quota, at, 1).is_ok());
+            assert!(breaker.check_at(&mut state, &quota, at, 1).is_err());
+            assert_eq!(Circuit::Closed(1), breaker.circuit(at));
+        }
+    }
+
+    #[test]
+    fn half_open_probe() {
+        let mut breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let quota = Quota::new(10, Duration::from_secs(1));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(Circuit::HalfOpen(None), breaker.circuit(later));
+
+        assert!(breaker.check_at(&mut state, &quota, later, 1).is_ok());
+        assert!(
+            breaker.check_at(&mut state, &quota, later, 1).is_err(),
+            "only a single probe at a time"
+        );
+
+        breaker.record_success();
+        assert_eq!(Circuit::Closed(0), breaker.circuit(later));
+        assert!(breaker.check_at(&mut state, &quota, later, 1).is_ok());
+
+        // a failed probe re-opens the circuit
+        breaker.record_failure_at(later);
+        let probe_at = later + Duration::from_secs(10);
+        assert!(breaker.check_at(&mut state, &quota, probe_at, 1).is_ok());
+        breaker.record_failure_at(probe_at);
+        assert_eq!(
+            Circuit::Open(Some(probe_at + Duration::from_secs(10))),
+            breaker.circuit(probe_at)
+        );
+    }
+
+    #[test]
+    fn open_forever() {
+        let mut breaker = CircuitBreaker::new(1, Duration::MAX);
+        let quota = Quota::new(10, Duration::from_secs(1));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        assert_eq!(Circuit::Open(None), breaker.circuit(now));
+        assert!(matches!(
+            breaker.check_at(&mut state, &quota, now + Duration::from_secs(3600), 1),
+            Err(Error::DeniedIndefinitely(1))
+        ));
+    }
+}
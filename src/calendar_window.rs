@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{Error, Quota, RateLimitAlgorithm};
+
+/// A fixed-window counter whose windows are aligned to the calendar, allowing
+/// `resource_limit` resources per `period`, as billing-style limits resetting at the
+/// top of every minute, hour or day are specified.
+///
+/// Windows are consecutive multiples of `period` since the UNIX epoch, so a period of a
+/// day resets at midnight UTC. [Instant]s are mapped to the calendar through an anchor,
+/// a pair of an [Instant] and the [SystemTime] it was taken at, which keeps windows
+/// stable when the system clock is adjusted later on.
+#[derive(Clone, Debug)]
+pub struct CalendarWindow {
+    anchor: Instant,
+    anchor_since_epoch: Duration,
+
+    /// Index of the current window since the UNIX epoch, unset for a new state.
+    pub window: Option<u64>,
+
+    /// Resources consumed in the current window.
+    pub count: u32,
+}
+
+impl CalendarWindow {
+    /// A new state mapping `anchor` to the calendar time `anchor_time`.
+    pub fn new(anchor: Instant, anchor_time: SystemTime) -> Self {
+        Self {
+            anchor,
+            anchor_since_epoch: anchor_time.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            window: None,
+            count: 0,
+        }
+    }
+
+    /// Nanoseconds since the UNIX epoch at `instant`.
+    fn since_epoch(&self, instant: Instant) -> u128 {
+        let anchor = self.anchor_since_epoch.as_nanos();
+        match instant.checked_duration_since(self.anchor) {
+            Some(after) => anchor + after.as_nanos(),
+            None => anchor.saturating_sub((self.anchor - instant).as_nanos()),
+        }
+    }
+
+    /// Index of the window `now` falls in.
+    fn index(&self, rate_limit: &Quota, now: Instant) -> u64 {
+        (self.since_epoch(now) / rate_limit.period.as_nanos()) as u64
+    }
+
+    /// Resources consumed in the window `now` falls in.
+    fn consumed(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        match self.window {
+            Some(window) if window == self.index(rate_limit, now) => self.count,
+            _ => 0,
+        }
+    }
+
+    /// When the window `now` falls in ends, `None` past what an [Instant] can represent.
+    fn window_end(&self, rate_limit: &Quota, now: Instant) -> Option<Instant> {
+        let period = rate_limit.period.as_nanos();
+        let end = (self.since_epoch(now) / period + 1) * period;
+        let until_end = end - self.since_epoch(now);
+        let until_end = match u64::try_from(until_end / 1_000_000_000) {
+            Ok(secs) => Duration::new(secs, (until_end % 1_000_000_000) as u32),
+            Err(_) => Duration::MAX,
+        };
+        now.checked_add(until_end)
+    }
+}
+
+impl Default for CalendarWindow {
+    /// A new state anchored at the current time.
+    fn default() -> Self {
+        Self::new(Instant::now(), SystemTime::now())
+    }
+}
+
+impl RateLimitAlgorithm for CalendarWindow {
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        if cost > rate_limit.resource_limit || rate_limit.is_zero() {
+            return Err(Error::DeniedIndefinitely(cost));
+        }
+        if rate_limit.is_unlimited() {
+            return Ok(());
+        }
+
+        match self.consumed(rate_limit, arrived_at).checked_add(cost) {
+            Some(count) if count <= rate_limit.resource_limit => {
+                self.window = Some(self.index(rate_limit, arrived_at));
+                self.count = count;
+                Ok(())
+            }
+            // Denied, must wait for the next window
+            _ => match self.window_end(rate_limit, arrived_at) {
+                Some(end) => Err(Error::DeniedUntil(end)),
+                None => Err(Error::TimeOverflow),
+            },
+        }
+    }
+
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return;
+        }
+
+        // Resources consumed by a past window are gone already
+        if self.consumed(rate_limit, arrived_at) > 0 {
+            self.count = self.count.saturating_sub(cost);
+        }
+    }
+
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return rate_limit.resource_limit;
+        }
+
+        rate_limit
+            .resource_limit
+            .saturating_sub(self.consumed(rate_limit, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calendar_window_resets_on_boundaries() {
+        let rate_limit = Quota::new(3, Duration::from_secs(60));
+        let anchor = Instant::now();
+        // 20 seconds into a minute
+        let mut window = CalendarWindow::new(
+            anchor,
+            UNIX_EPOCH + Duration::from_secs(28_333_333 * 60 + 20),
+        );
+
+        assert!(window.check_and_modify_at(&rate_limit, anchor, 3).is_ok());
+        assert!(
+            matches!(
+                window.check_and_modify_at(&rate_limit, anchor, 1),
+                Err(Error::DeniedUntil(next)) if next == anchor + Duration::from_secs(40)
+            ),
+            "denied until the top of the next minute"
+        );
+        assert_eq!(
+            3,
+            window.remaining_resources(&rate_limit, anchor + Duration::from_secs(40))
+        );
+        assert!(window
+            .check_and_modify_at(&rate_limit, anchor + Duration::from_secs(40), 3)
+            .is_ok());
+    }
+
+    #[test]
+    fn calendar_window_revert() {
+        let rate_limit = Quota::new(3, Duration::from_secs(60));
+        let mut window = CalendarWindow::default();
+        let now = Instant::now();
+
+        assert!(window.check_and_modify_at(&rate_limit, now, 2).is_ok());
+        window.revert_at(&rate_limit, now, 1);
+        assert_eq!(2, window.remaining_resources(&rate_limit, now));
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, now, 4),
+            Err(Error::DeniedIndefinitely(4))
+        ));
+    }
+
+    #[test]
+    fn calendar_window_overflows() {
+        let anchor = Instant::now();
+        let mut window = CalendarWindow::new(anchor, UNIX_EPOCH);
+
+        let rate_limit = Quota::new(u32::MAX, Duration::from_secs(60));
+        assert!(window
+            .check_and_modify_at(&rate_limit, anchor, u32::MAX)
+            .is_ok());
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, anchor, 1),
+            Err(Error::DeniedUntil(next)) if next == anchor + rate_limit.period
+        ));
+
+        let rate_limit = Quota::new(1, Duration::MAX);
+        let mut window = CalendarWindow::new(anchor, UNIX_EPOCH);
+        assert!(window.check_and_modify_at(&rate_limit, anchor, 1).is_ok());
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, anchor, 1),
+            Err(Error::TimeOverflow)
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+/// A source of [Instant]s used to drive the `*_at` methods of [`State`](crate::State).
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// Reads the time with [Instant::now] on every call.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A virtual clock which only moves when told to, for simulations and tests.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: AtomicU64,
+}
+
+impl ManualClock {
+    /// A clock reading `start` until advanced.
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            elapsed: AtomicU64::new(0),
+        }
+    }
+
+    /// Time elapsed since `start`.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed.load(Ordering::Relaxed))
+    }
+
+    /// Moves the clock forward by `duration`, saturating at [u64::MAX] nanoseconds after
+    /// `start`.
+    pub fn advance(&self, duration: Duration) {
+        let duration = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .elapsed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |elapsed| {
+                Some(elapsed.saturating_add(duration))
+            });
+    }
+
+    /// Moves the clock to `elapsed` after `start`, which may go backwards, saturating at
+    /// [u64::MAX] nanoseconds.
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        let elapsed = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed.store(elapsed, Ordering::Relaxed);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl Clock for ManualClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+#[derive(Debug)]
+struct Cached {
+    start: Instant,
+    elapsed: AtomicU64,
+}
+
+impl Cached {
+    fn refresh(&self) {
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        self.elapsed.store(elapsed, Ordering::Relaxed);
+    }
+}
+
+/// A cached "now", refreshed by a background thread every `resolution`.
+///
+/// Reading it is a single atomic load, which is a lot cheaper than [Instant::now]
+/// on hot paths, at the cost of lagging behind the real time by up to `resolution`.
+/// A lagging clock only delays refills, so checks never under-enforce a quota.
+///
+/// Clones share the same cache, the background thread exits once every clone is dropped.
+#[derive(Clone, Debug)]
+pub struct CoarseClock {
+    cached: Arc<Cached>,
+}
+
+impl CoarseClock {
+    /// Starts a new OS thread refreshing the cache, so share a clock through its clones
+    /// rather than creating one per limiter.
+    ///
+    /// # Panics
+    /// If the resolution is zero, as the thread would spin on a core.
+    pub fn new(resolution: Duration) -> Self {
+        assert!(
+            !resolution.is_zero(),
+            "resolution must be greater than zero"
+        );
+
+        let cached = Arc::new(Cached {
+            start: Instant::now(),
+            elapsed: AtomicU64::new(0),
+        });
+
+        let weak = Arc::downgrade(&cached);
+        std::thread::Builder::new()
+            .name("gcra-coarse-clock".into())
+            .spawn(move || refresh_loop(weak, resolution))
+            .expect("spawn coarse clock thread");
+
+        Self { cached }
+    }
+
+    /// The cached time, refreshed at most `resolution` ago.
+    #[inline]
+    pub fn now(&self) -> Instant {
+        self.cached.start + Duration::from_nanos(self.cached.elapsed.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for CoarseClock {
+    /// A [CoarseClock] with millisecond resolution.
+    fn default() -> Self {
+        Self::new(Duration::from_millis(1))
+    }
+}
+
+impl Clock for CoarseClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        CoarseClock::now(self)
+    }
+}
+
+fn refresh_loop(cached: Weak<Cached>, resolution: Duration) {
+    loop {
+        std::thread::sleep(resolution);
+
+        match cached.upgrade() {
+            Some(cached) => cached.refresh(),
+            None => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock() {
+        let start = Instant::now();
+        let clock = ManualClock::new(start);
+        assert_eq!(start, clock.now());
+
+        clock.advance(Duration::from_secs(1));
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(start + Duration::from_millis(1500), clock.now());
+
+        clock.set_elapsed(Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(100), clock.elapsed());
+
+        clock.advance(Duration::from_secs(u64::MAX));
+        assert_eq!(Duration::from_nanos(u64::MAX), clock.elapsed());
+        clock.set_elapsed(Duration::MAX);
+        assert_eq!(Duration::from_nanos(u64::MAX), clock.elapsed());
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn coarse_clock_zero_resolution() {
+        CoarseClock::new(Duration::ZERO);
+    }
+
+    #[test]
+    fn coarse_clock_advances() {
+        let clock = CoarseClock::new(Duration::from_millis(1));
+        let first = clock.now();
+        assert!(first <= Instant::now());
+
+        std::thread::sleep(Duration::from_millis(20));
+        let second = clock.now();
+        assert!(second > first, "cached time should have been refreshed");
+        assert!(second <= Instant::now(), "cached time never runs ahead");
+    }
+
+    #[test]
+    fn coarse_clock_shared_between_clones() {
+        let clock = CoarseClock::default();
+        let cloned = clock.clone();
+
+        std::thread::sleep(Duration::from_millis(5));
+        let a = clock.now();
+        let b = cloned.now();
+        assert!(a.max(b) - a.min(b) <= Duration::from_millis(5));
+    }
+}
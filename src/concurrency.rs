@@ -0,0 +1,145 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+#[derive(Debug)]
+pub enum AcquireError {
+    /// Denied by the rate limit
+    Rate(Error),
+
+    /// Too many permits are in flight already
+    Concurrency(usize),
+}
+
+impl Display for AcquireError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcquireError::Rate(err) => Display::fmt(err, fmt),
+            AcquireError::Concurrency(max) => {
+                write!(fmt, "at most {} permits can be in flight", max)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: State,
+    in_flight: usize,
+}
+
+/// Couples a [Quota] with a cap on how many permits are in flight, i.e.
+/// "at most `max_in_flight` at once AND at most `resource_limit` per `period`".
+///
+/// Both limits are checked under one lock, so a permit is only granted, and the
+/// resources booked, when both allow it.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    quota: Quota,
+    max_in_flight: usize,
+    inner: Mutex<Inner>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(quota: Quota, max_in_flight: usize) -> Self {
+        Self {
+            quota,
+            max_in_flight,
+            inner: Mutex::new(Inner {
+                state: State::default(),
+                in_flight: 0,
+            }),
+        }
+    }
+
+    /// Simply passes the current Instant to [`try_acquire_at()`](ConcurrencyLimiter::try_acquire_at)
+    #[inline]
+    pub fn try_acquire(&self, cost: u32) -> Result<Permit<'_>, AcquireError> {
+        self.try_acquire_at(Instant::now(), cost)
+    }
+
+    /// Acquires a permit if both the concurrency cap and the quota allow it.
+    /// The permit is given back when dropped, the consumed resources are not.
+    pub fn try_acquire_at(
+        &self,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<Permit<'_>, AcquireError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        if inner.in_flight >= self.max_in_flight {
+            return Err(AcquireError::Concurrency(self.max_in_flight));
+        }
+
+        inner
+            .state
+            .check_and_modify_at(&self.quota, arrived_at, cost)
+            .map_err(AcquireError::Rate)?;
+        inner.in_flight += 1;
+
+        Ok(Permit { limiter: self })
+    }
+
+    /// Amount of permits currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .in_flight
+    }
+}
+
+/// A permit of a [ConcurrencyLimiter], released on drop.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    limiter: &'a ConcurrencyLimiter,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut inner = self
+            .limiter
+            .inner
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        inner.in_flight -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn concurrency_capped() {
+        let limiter = ConcurrencyLimiter::new(Quota::new(10, Duration::from_secs(1)), 2);
+        let now = Instant::now();
+
+        let first = limiter.try_acquire_at(now, 1).unwrap();
+        let _second = limiter.try_acquire_at(now, 1).unwrap();
+        assert!(matches!(
+            limiter.try_acquire_at(now, 1),
+            Err(AcquireError::Concurrency(2))
+        ));
+        assert_eq!(2, limiter.in_flight());
+
+        drop(first);
+        assert!(limiter.try_acquire_at(now, 1).is_ok());
+    }
+
+    #[test]
+    fn rate_limited() {
+        let limiter = ConcurrencyLimiter::new(Quota::new(1, Duration::from_secs(1)), 2);
+        let now = Instant::now();
+
+        let _permit = limiter.try_acquire_at(now, 1).unwrap();
+        assert!(matches!(
+            limiter.try_acquire_at(now, 1),
+            Err(AcquireError::Rate(Error::DeniedUntil(_)))
+        ));
+        assert_eq!(1, limiter.in_flight(), "denied acquires hold no permit");
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::{Debug, Formatter};
+use std::time::{Duration, Instant};
+
+/// Tracks the share of denied checks over a sliding window, calling back once it crosses
+/// a threshold, e.g. to alert on or ban a key denied more than half of the time over the
+/// last minute.
+///
+/// Meant to be kept next to the [State](crate::State) of each key, like a
+/// [ThroughputEstimator](crate::ThroughputEstimator). The window slides by weighting the
+/// counts of the previous fixed window by how much of it still overlaps, so memory stays
+/// constant.
+///
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::sync::Arc;
+/// use std::time::{Duration, Instant};
+/// use gcra::{DenialTracker, Quota, State};
+///
+/// let alerts = Arc::new(AtomicU32::new(0));
+/// let fired = Arc::clone(&alerts);
+/// let mut tracker = DenialTracker::new(Duration::from_secs(60), 0.5)
+///     .on_alert(move |_ratio| {
+///         fired.fetch_add(1, Ordering::Relaxed);
+///     });
+///
+/// let quota = Quota::new(1, Duration::from_secs(60));
+/// let mut state = State::default();
+/// let now = Instant::now();
+/// for _ in 0..3 {
+///     let denied = state.check_and_modify_at(&quota, now, 1).is_err();
+///     tracker.record_at(now, denied);
+/// }
+/// assert_eq!(1, alerts.load(Ordering::Relaxed));
+/// ```
+pub struct DenialTracker {
+    window: Duration,
+    threshold: f64,
+    min_checks: u32,
+
+    /// Start of the current window, unset before the first check
+    start: Option<Instant>,
+    /// Checks and denials in the current window
+    current: (u32, u32),
+    /// Checks and denials in the previous window
+    previous: (u32, u32),
+
+    alerting: bool,
+    on_alert: Option<Box<dyn FnMut(f64) + Send>>,
+}
+
+impl DenialTracker {
+    /// Alerts once more than `threshold` of the checks in the trailing `window` are denied.
+    ///
+    /// # Panics
+    /// If the window is zero.
+    pub fn new(window: Duration, threshold: f64) -> Self {
+        assert!(!window.is_zero(), "window must be greater than zero");
+
+        Self {
+            window,
+            threshold,
+            min_checks: 1,
+            start: None,
+            current: (0, 0),
+            previous: (0, 0),
+            alerting: false,
+            on_alert: None,
+        }
+    }
+
+    /// Only alerts once the window holds at least `min_checks` checks, so a few denials
+    /// of a quiet key don't alert.
+    pub fn with_min_checks(mut self, min_checks: u32) -> Self {
+        self.min_checks = min_checks;
+        self
+    }
+
+    /// Calls `on_alert` with the denial ratio when it crosses the threshold. It isn't
+    /// called again until the ratio drops back to the threshold and crosses it anew.
+    pub fn on_alert(mut self, on_alert: impl FnMut(f64) + Send + 'static) -> Self {
+        self.on_alert = Some(Box::new(on_alert));
+        self
+    }
+
+    /// Records a check at `now`, and whether it was denied.
+    pub fn record_at(&mut self, now: Instant, denied: bool) {
+        self.slide(now);
+        self.current.0 = self.current.0.saturating_add(1);
+        self.current.1 = self.current.1.saturating_add(denied as u32);
+
+        let (checks, ratio) = self.weighted_at(now);
+        if ratio <= self.threshold || checks < self.min_checks as f64 {
+            self.alerting = false;
+        } else if !self.alerting {
+            self.alerting = true;
+            if let Some(on_alert) = &mut self.on_alert {
+                on_alert(ratio);
+            }
+        }
+    }
+
+    /// Share of the checks denied over the window ending at `now`, `0.0` without checks.
+    pub fn ratio_at(&self, now: Instant) -> f64 {
+        self.weighted_at(now).1
+    }
+
+    /// True if the last recorded check left the ratio above the threshold.
+    pub fn is_alerting(&self) -> bool {
+        self.alerting
+    }
+
+    /// Moves the current window forward so it contains `now`.
+    fn slide(&mut self, now: Instant) {
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                self.start = Some(now);
+                return;
+            }
+        };
+
+        let windows = now.saturating_duration_since(start).as_nanos() / self.window.as_nanos();
+        match windows {
+            0 => return,
+            1 => self.previous = self.current,
+            _ => self.previous = (0, 0),
+        }
+        self.current = (0, 0);
+        self.start = Some(match u32::try_from(windows) {
+            Ok(windows) => start + self.window.saturating_mul(windows),
+            Err(_) => now,
+        });
+    }
+
+    /// Weighted checks and denial ratio over the window ending at `now`.
+    fn weighted_at(&self, now: Instant) -> (f64, f64) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return (0.0, 0.0),
+        };
+
+        // the window `now` falls in, counted from the current one
+        let elapsed = now.saturating_duration_since(start).as_nanos();
+        let window = self.window.as_nanos();
+        let (previous, current) = match elapsed / window {
+            0 => (self.previous, self.current),
+            1 => (self.current, (0, 0)),
+            _ => return (0.0, 0.0),
+        };
+        let overlap = 1.0 - (elapsed % window) as f64 / window as f64;
+
+        let checks = previous.0 as f64 * overlap + current.0 as f64;
+        let denied = previous.1 as f64 * overlap + current.1 as f64;
+        if checks == 0.0 {
+            (0.0, 0.0)
+        } else {
+            (checks, denied / checks)
+        }
+    }
+}
+
+impl Debug for DenialTracker {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("DenialTracker")
+            .field("window", &self.window)
+            .field("threshold", &self.threshold)
+            .field("min_checks", &self.min_checks)
+            .field("start", &self.start)
+            .field("current", &self.current)
+            .field("previous", &self.previous)
+            .field("alerting", &self.alerting)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn alerts_once_per_crossing() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let fired = Arc::clone(&alerts);
+        let mut tracker = DenialTracker::new(Duration::from_secs(60), 0.5)
+            .with_min_checks(4)
+            .on_alert(move |ratio| fired.lock().unwrap().push(ratio));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            tracker.record_at(now, true);
+        }
+        assert!(!tracker.is_alerting(), "too few checks to alert");
+
+        tracker.record_at(now, true);
+        tracker.record_at(now, true);
+        assert!(tracker.is_alerting());
+        assert_eq!(vec![1.0], *alerts.lock().unwrap());
+
+        // back to the threshold re-arms the alert
+        for _ in 0..5 {
+            tracker.record_at(now, false);
+        }
+        assert!(!tracker.is_alerting());
+        tracker.record_at(now, true);
+        assert_eq!(2, alerts.lock().unwrap().len());
+    }
+
+    #[test]
+    fn window_slides() {
+        let mut tracker = DenialTracker::new(Duration::from_secs(60), 0.5);
+        let now = Instant::now();
+        assert_eq!(0.0, tracker.ratio_at(now));
+
+        tracker.record_at(now, true);
+        tracker.record_at(now, false);
+        assert_eq!(0.5, tracker.ratio_at(now));
+
+        // half of the previous window still counts
+        let later = now + Duration::from_secs(90);
+        tracker.record_at(later, false);
+        assert_eq!(0.25, tracker.ratio_at(later));
+
+        assert_eq!(0.0, tracker.ratio_at(later + Duration::from_secs(120)));
+    }
+}
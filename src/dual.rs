@@ -0,0 +1,147 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+#[derive(Debug, Default)]
+struct Inner {
+    requests: State,
+    tokens: State,
+}
+
+/// Enforces a requests quota and a tokens quota together, as LLM APIs limit both
+/// requests and tokens per minute.
+///
+/// Each call costs one request plus the tokens it declares. Both quotas are checked
+/// under one lock, and resources are only consumed when both allow the call.
+#[derive(Debug)]
+pub struct DualLimiter {
+    requests: Quota,
+    tokens: Quota,
+    inner: Mutex<Inner>,
+}
+
+impl DualLimiter {
+    pub fn new(requests: Quota, tokens: Quota) -> Self {
+        Self {
+            requests,
+            tokens,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Simply passes the current Instant to [`check_at()`](DualLimiter::check_at)
+    #[inline]
+    pub fn check(&self, tokens: u32) -> Result<(), Error> {
+        self.check_at(Instant::now(), tokens)
+    }
+
+    /// Checks a call of `tokens` against both quotas, consuming from both or neither.
+    /// A call of no tokens is only checked against the requests quota.
+    ///
+    /// # Returns
+    /// When denied by both quotas, the error of the one denying the longest, where
+    /// [Error::DeniedIndefinitely] beats [Error::DeniedUntil].
+    pub fn check_at(&self, arrived_at: Instant, tokens: u32) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut next_requests = inner.requests.clone();
+        let requests = next_requests.check_and_modify_at(&self.requests, arrived_at, 1);
+        // a call of no tokens only needs a request, rather than probing the tokens quota
+        let mut next_tokens = inner.tokens.clone();
+        let tokens = match tokens {
+            0 => Ok(()),
+            _ => next_tokens.check_and_modify_at(&self.tokens, arrived_at, tokens),
+        };
+
+        match (requests, tokens) {
+            (Ok(()), Ok(())) => {
+                inner.requests = next_requests;
+                inner.tokens = next_tokens;
+                Ok(())
+            }
+            (Err(Error::DeniedUntil(a)), Err(Error::DeniedUntil(b))) => {
+                Err(Error::DeniedUntil(a.max(b)))
+            }
+            (Err(Error::DeniedUntil(_)), Err(err)) | (Err(err), _) | (_, Err(err)) => Err(err),
+        }
+    }
+
+    /// Gives back `unused` tokens, e.g. when a call used fewer tokens than it declared.
+    pub fn refund_tokens_at(&self, now: Instant, unused: u32) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.tokens.revert_at(&self.tokens, now, unused);
+    }
+
+    /// Remaining requests and tokens at `now`.
+    pub fn remaining_at(&self, now: Instant) -> (u32, u32) {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        (
+            inner.requests.remaining_resources(&self.requests, now),
+            inner.tokens.remaining_resources(&self.tokens, now),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn dual_limiter_both_or_neither() {
+        let limiter = DualLimiter::new(
+            Quota::new(3, Duration::from_secs(60)),
+            Quota::new(1000, Duration::from_secs(60)),
+        );
+        let now = Instant::now();
+
+        assert!(limiter.check_at(now, 600).is_ok());
+        assert!(
+            limiter.check_at(now, 600).is_err(),
+            "denied by the tokens quota"
+        );
+        assert_eq!((2, 400), limiter.remaining_at(now), "nothing was consumed");
+
+        assert!(limiter.check_at(now, 100).is_ok());
+        assert!(limiter.check_at(now, 100).is_ok());
+        assert!(matches!(
+            limiter.check_at(now, 100),
+            Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(20)
+        ));
+        assert!(matches!(
+            limiter.check_at(now, 2000),
+            Err(Error::DeniedIndefinitely(2000))
+        ));
+    }
+
+    #[test]
+    fn dual_limiter_refund_tokens() {
+        let limiter = DualLimiter::new(
+            Quota::new(10, Duration::from_secs(60)),
+            Quota::new(1000, Duration::from_secs(60)),
+        );
+        let now = Instant::now();
+
+        limiter.check_at(now, 800).unwrap();
+        limiter.refund_tokens_at(now, 500);
+        assert_eq!((9, 700), limiter.remaining_at(now));
+    }
+
+    #[test]
+    fn dual_limiter_zero_tokens() {
+        let limiter = DualLimiter::new(
+            Quota::new(10, Duration::from_secs(60)),
+            Quota::new(1000, Duration::from_secs(60)),
+        );
+        let now = Instant::now();
+
+        limiter.check_at(now, 1000).unwrap();
+        assert!(
+            limiter.check_at(now, 0).is_ok(),
+            "no tokens are needed while the tokens quota is exhausted"
+        );
+        assert_eq!((8, 0), limiter.remaining_at(now));
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, RateLimitAlgorithm};
+
+/// A fixed-window counter, allowing `resource_limit` resources per `period`.
+///
+/// A window starts with the first arrival after the previous one ended. Unlike GCRA it
+/// allows up to twice the limit around a window boundary, it exists mostly for comparing
+/// with, and migrating from, existing fixed-window deployments.
+#[derive(Clone, Debug, Default)]
+pub struct FixedWindow {
+    /// Start of the current window, unset for a new state.
+    pub window_start: Option<Instant>,
+
+    /// Resources consumed in the current window.
+    pub count: u32,
+}
+
+impl FixedWindow {
+    /// The window `now` falls in, if any: its start and consumed resources.
+    fn current(&self, rate_limit: &Quota, now: Instant) -> Option<(Instant, u32)> {
+        let start = self.window_start?;
+        match start.checked_add(rate_limit.period) {
+            Some(end) if now >= end => None,
+            // a window too long to end never does
+            _ => Some((start, self.count)),
+        }
+    }
+}
+
+impl RateLimitAlgorithm for FixedWindow {
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        if cost > rate_limit.resource_limit || rate_limit.is_zero() {
+            return Err(Error::DeniedIndefinitely(cost));
+        }
+        if rate_limit.is_unlimited() {
+            return Ok(());
+        }
+
+        let (start, count) = self
+            .current(rate_limit, arrived_at)
+            .unwrap_or((arrived_at, 0));
+
+        match count.checked_add(cost) {
+            Some(count) if count <= rate_limit.resource_limit => {
+                self.window_start = Some(start);
+                self.count = count;
+                Ok(())
+            }
+            // Denied, must wait for the next window
+            _ => match start.checked_add(rate_limit.period) {
+                Some(end) => Err(Error::DeniedUntil(end)),
+                None => Err(Error::TimeOverflow),
+            },
+        }
+    }
+
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return;
+        }
+
+        // Resources consumed by an expired window are gone already
+        if self.current(rate_limit, arrived_at).is_some() {
+            self.count = self.count.saturating_sub(cost);
+        }
+    }
+
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return rate_limit.resource_limit;
+        }
+
+        match self.current(rate_limit, now) {
+            Some((_, count)) => rate_limit.resource_limit.saturating_sub(count),
+            None => rate_limit.resource_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn fixed_window_limited() {
+        let rate_limit = Quota::new(3, Duration::from_secs(1));
+        let mut window = FixedWindow::default();
+        let now = Instant::now();
+
+        for i in 0..3 {
+            assert!(
+                window.check_and_modify_at(&rate_limit, now, 1).is_ok(),
+                "request #{} should pass",
+                i + 1
+            );
+        }
+        assert_eq!(0, window.remaining_resources(&rate_limit, now));
+
+        assert!(
+            matches!(
+                window.check_and_modify_at(&rate_limit, now + Duration::from_millis(500), 1),
+                Err(Error::DeniedUntil(next)) if next == now + rate_limit.period
+            ),
+            "request #4 should be denied until the window ends"
+        );
+
+        assert!(
+            window
+                .check_and_modify_at(&rate_limit, now + rate_limit.period, 3)
+                .is_ok(),
+            "the next window starts empty"
+        );
+    }
+
+    #[test]
+    fn fixed_window_revert() {
+        let rate_limit = Quota::new(3, Duration::from_secs(1));
+        let mut window = FixedWindow::default();
+        let now = Instant::now();
+
+        assert!(window.check_and_modify_at(&rate_limit, now, 3).is_ok());
+        window.revert_at(&rate_limit, now, 1);
+        assert_eq!(1, window.remaining_resources(&rate_limit, now));
+
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, now, 4),
+            Err(Error::DeniedIndefinitely(4))
+        ));
+    }
+
+    #[test]
+    fn fixed_window_overflows() {
+        let now = Instant::now();
+
+        let rate_limit = Quota::new(u32::MAX, Duration::from_secs(60));
+        let mut window = FixedWindow::default();
+        assert!(window
+            .check_and_modify_at(&rate_limit, now, u32::MAX)
+            .is_ok());
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, now, 1),
+            Err(Error::DeniedUntil(next)) if next == now + rate_limit.period
+        ));
+
+        let rate_limit = Quota::new(2, Duration::MAX);
+        let mut window = FixedWindow::default();
+        assert!(window.check_and_modify_at(&rate_limit, now, 2).is_ok());
+        assert!(matches!(
+            window.check_and_modify_at(&rate_limit, now, 1),
+            Err(Error::TimeOverflow)
+        ));
+        assert_eq!(0, window.remaining_resources(&rate_limit, now));
+    }
+
+    #[test]
+    fn fixed_window_sentinels() {
+        let mut window = FixedWindow::default();
+        let now = Instant::now();
+
+        assert!(matches!(
+            window.check_and_modify_at(&Quota::zero(), now, 0),
+            Err(Error::DeniedIndefinitely(0))
+        ));
+        for _ in 0..3 {
+            assert!(window
+                .check_and_modify_at(&Quota::unlimited(), now, u32::MAX)
+                .is_ok());
+        }
+        assert_eq!(None, window.window_start);
+    }
+}
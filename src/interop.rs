@@ -0,0 +1,159 @@
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+use governor::nanos::Nanos;
+
+use crate::{Error, Quota, State};
+
+/// A quota governor has no equivalent for: [`Quota::zero()`], [`Quota::unlimited()`] and
+/// zero bursts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedQuota(pub Quota);
+
+impl Display for UnsupportedQuota {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "governor has no quota for {}", self.0)
+    }
+}
+
+/// Replenishes one resource per emission interval, up to the burst. Lossless, since
+/// governor keeps the same two numbers.
+impl TryFrom<Quota> for governor::Quota {
+    type Error = UnsupportedQuota;
+
+    fn try_from(quota: Quota) -> Result<Self, Self::Error> {
+        if quota.is_zero() || quota.is_unlimited() {
+            return Err(UnsupportedQuota(quota));
+        }
+
+        let burst = NonZeroU32::new(quota.burst).ok_or(UnsupportedQuota(quota))?;
+        governor::Quota::with_period(quota.emission_interval)
+            .map(|governor| governor.allow_burst(burst))
+            .ok_or(UnsupportedQuota(quota))
+    }
+}
+
+/// A quota of a burst per the time it takes to replenish, with the same emission
+/// interval. The period saturates for bursts that don't fit in a [Duration].
+impl From<governor::Quota> for Quota {
+    fn from(quota: governor::Quota) -> Self {
+        let burst = quota.burst_size().get();
+        Self {
+            resource_limit: burst,
+            period: quota.replenish_interval().saturating_mul(burst),
+            emission_interval: quota.replenish_interval(),
+            burst,
+        }
+    }
+}
+
+impl State {
+    /// The state a governor state store holds as `tat`, for a limiter whose clock counts
+    /// from `start`, so states can move over while both run side by side.
+    ///
+    /// Both keep the same TAT, governor as nanoseconds since `start`, so a custom
+    /// `governor::state::StateStore` can be backed by [State]s and the other way round.
+    pub fn from_governor(tat: Option<Nanos>, start: Instant) -> Result<Self, Error> {
+        let tat = match tat {
+            Some(tat) => Some(
+                start
+                    .checked_add(Duration::from(tat))
+                    .ok_or(Error::TimeOverflow)?,
+            ),
+            None => None,
+        };
+
+        Ok(Self { tat })
+    }
+
+    /// The TAT as a governor state store holds it, for a limiter whose clock counts from
+    /// `start`.
+    ///
+    /// A TAT not past `start` is `None`, which governor treats as a fresh state, the
+    /// same as any TAT in its past.
+    pub fn to_governor(&self, start: Instant) -> Option<Nanos> {
+        let since = self.tat?.checked_duration_since(start)?;
+        let nanos = u64::try_from(since.as_nanos()).unwrap_or(u64::MAX);
+        (nanos > 0).then(|| Nanos::new(nanos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_round_trip() {
+        let quota = Quota::new(10, Duration::from_secs(10)).with_burst(5);
+        let governor = governor::Quota::try_from(quota).unwrap();
+        assert_eq!(Duration::from_secs(1), governor.replenish_interval());
+        assert_eq!(5, governor.burst_size().get());
+
+        let back = Quota::from(governor);
+        assert_eq!(quota.emission_interval, back.emission_interval);
+        assert_eq!(quota.burst, back.burst);
+        assert_eq!(
+            quota.delay_variation_tolerance(),
+            back.delay_variation_tolerance()
+        );
+    }
+
+    #[test]
+    fn unsupported_quotas() {
+        let mut no_burst = Quota::new(1, Duration::from_secs(1));
+        no_burst.burst = 0;
+        for quota in [Quota::zero(), Quota::unlimited(), no_burst] {
+            assert_eq!(
+                Err(UnsupportedQuota(quota)),
+                governor::Quota::try_from(quota)
+            );
+        }
+
+        let quota = Quota::from(governor::Quota::with_period(Duration::MAX).unwrap());
+        assert_eq!(Duration::MAX, quota.period);
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let start = Instant::now();
+        let mut state = State::default();
+        assert_eq!(None, state.to_governor(start));
+
+        state
+            .check_and_modify_at(&quota, start + Duration::from_secs(1), 2)
+            .unwrap();
+        let tat = state.to_governor(start);
+        assert_eq!(Some(Nanos::new(3_000_000_000)), tat);
+
+        let mut back = State::from_governor(tat, start).unwrap();
+        assert_eq!(state.tat, back.tat);
+        assert!(back
+            .check_and_modify_at(&quota, start + Duration::from_secs(1), 1)
+            .is_err());
+
+        assert_eq!(
+            None,
+            state.to_governor(start + Duration::from_secs(3)),
+            "a TAT in the past is a fresh state"
+        );
+        assert!(matches!(
+            State::from_governor(Some(Nanos::new(u64::MAX)), far_future()),
+            Err(Error::TimeOverflow)
+        ));
+    }
+
+    /// The latest [Instant] there is.
+    fn far_future() -> Instant {
+        let mut instant = Instant::now();
+        let mut step = Duration::from_secs(1 << 62);
+        while !step.is_zero() {
+            match instant.checked_add(step) {
+                Some(later) => instant = later,
+                None => step /= 2,
+            }
+        }
+        instant
+    }
+}
@@ -0,0 +1,250 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Aggregates client addresses into network prefixes, so per-IP limits can't be dodged
+/// by rotating addresses within a subnet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpPrefix {
+    v4: u8,
+    v6: u8,
+}
+
+impl IpPrefix {
+    /// # Panics
+    /// If a prefix length exceeds the length of its addresses.
+    pub const fn new(v4: u8, v6: u8) -> Self {
+        assert!(v4 <= 32, "IPv4 prefix must be at most 32 bits");
+        assert!(v6 <= 128, "IPv6 prefix must be at most 128 bits");
+
+        Self { v4, v6 }
+    }
+
+    /// Prefix length of IPv4 addresses, at most 32.
+    pub const fn v4(&self) -> u8 {
+        self.v4
+    }
+
+    /// Prefix length of IPv6 addresses, at most 128.
+    pub const fn v6(&self) -> u8 {
+        self.v6
+    }
+
+    /// The network of `addr`, to be used as the key of its state.
+    ///
+    /// IPv4-mapped IPv6 addresses count as IPv4, so both notations of a client share
+    /// the same key.
+    pub fn key(&self, addr: IpAddr) -> IpAddr {
+        match canonical(addr) {
+            IpAddr::V4(addr) => {
+                let mask = u32::MAX.checked_shl(32 - self.v4 as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+            }
+            IpAddr::V6(addr) => {
+                let mask = u128::MAX.checked_shl(128 - self.v6 as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+            }
+        }
+    }
+}
+
+impl Default for IpPrefix {
+    /// IPv4 /24 and IPv6 /64 networks, the smallest ones commonly handed to one client.
+    fn default() -> Self {
+        Self::new(24, 64)
+    }
+}
+
+/// Finds the address of the client behind reverse proxies from the `Forwarded` or
+/// `X-Forwarded-For` headers, only believing hops added by trusted proxies, so clients
+/// can't pick their own key by spoofing the headers.
+///
+/// Hops are walked from the peer backwards, and the first one which isn't a trusted
+/// proxy is the client.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientIp {
+    hops: usize,
+    trusted: Vec<(IpAddr, u8)>,
+}
+
+impl ClientIp {
+    /// Trusts no proxy, i.e. the client is always the peer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts the `hops` proxies closest to us, whatever their address, e.g. `1` behind a
+    /// single load balancer.
+    pub fn hops(mut self, hops: usize) -> Self {
+        self.hops = hops;
+        self
+    }
+
+    /// Trusts proxies within the `network`/`prefix_len` network.
+    ///
+    /// # Panics
+    /// If the prefix length exceeds the length of the network's addresses.
+    pub fn trust(mut self, network: IpAddr, prefix_len: u8) -> Self {
+        let network = canonical(network);
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        assert!(
+            prefix_len <= max,
+            "prefix must not exceed the address length"
+        );
+
+        self.trusted.push((network, prefix_len));
+        self
+    }
+
+    /// The client address of a request from `peer`, given its `Forwarded` and
+    /// `X-Forwarded-For` header values, where `Forwarded` takes precedence.
+    ///
+    /// An unparseable hop stops the walk at the proxy which added it.
+    pub fn extract(
+        &self,
+        peer: IpAddr,
+        forwarded: Option<&str>,
+        x_forwarded_for: Option<&str>,
+    ) -> IpAddr {
+        let hops: Vec<Option<IpAddr>> = match (forwarded, x_forwarded_for) {
+            (Some(forwarded), _) => forwarded.split(',').map(forwarded_for).collect(),
+            (None, Some(x_forwarded_for)) => x_forwarded_for.split(',').map(parse_addr).collect(),
+            (None, None) => Vec::new(),
+        };
+
+        let mut client = canonical(peer);
+        for (depth, hop) in hops.into_iter().rev().enumerate() {
+            if !self.is_trusted(depth, client) {
+                break;
+            }
+            match hop {
+                Some(hop) => client = canonical(hop),
+                None => break,
+            }
+        }
+        client
+    }
+
+    fn is_trusted(&self, depth: usize, addr: IpAddr) -> bool {
+        depth < self.hops
+            || self
+                .trusted
+                .iter()
+                .any(|(network, prefix_len)| match (network, addr) {
+                    (IpAddr::V4(_), IpAddr::V4(_)) => {
+                        let prefix = IpPrefix::new(*prefix_len, 0);
+                        prefix.key(*network) == prefix.key(addr)
+                    }
+                    (IpAddr::V6(_), IpAddr::V6(_)) => {
+                        let prefix = IpPrefix::new(0, *prefix_len);
+                        prefix.key(*network) == prefix.key(addr)
+                    }
+                    _ => false,
+                })
+    }
+}
+
+/// The `for` parameter of a `Forwarded` element, `None` if missing or obfuscated.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("for") {
+            parse_addr(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses an address, optionally with a port, IPv6 ones in brackets when they have one.
+fn parse_addr(addr: &str) -> Option<IpAddr> {
+    let addr = addr.trim();
+    if let Ok(addr) = addr.parse() {
+        return Some(addr);
+    }
+
+    match addr.strip_prefix('[') {
+        Some(rest) => rest.split_once(']')?.0.parse().ok(),
+        None => addr
+            .rsplit_once(':')?
+            .0
+            .parse::<Ipv4Addr>()
+            .ok()
+            .map(IpAddr::V4),
+    }
+}
+
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => addr,
+        },
+        addr => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_prefix_key() {
+        let prefix = IpPrefix::default();
+
+        let a: IpAddr = "203.0.113.7".parse().unwrap();
+        let b: IpAddr = "::ffff:203.0.113.200".parse().unwrap();
+        assert_eq!("203.0.113.0".parse::<IpAddr>().unwrap(), prefix.key(a));
+        assert_eq!(prefix.key(a), prefix.key(b));
+
+        let v6: IpAddr = "2001:db8:1:2:3:4:5:6".parse().unwrap();
+        assert_eq!("2001:db8:1:2::".parse::<IpAddr>().unwrap(), prefix.key(v6));
+        assert_eq!((24, 64), (prefix.v4(), prefix.v6()));
+    }
+
+    #[test]
+    fn client_ip_trusted_proxies() {
+        let peer: IpAddr = "10.0.0.2".parse().unwrap();
+        let client_ip = ClientIp::new().trust("10.0.0.0".parse().unwrap(), 8);
+
+        assert_eq!(
+            peer,
+            ClientIp::new().extract(peer, None, Some("198.51.100.1")),
+            "headers are ignored without trusted proxies"
+        );
+        assert_eq!(
+            "203.0.113.7".parse::<IpAddr>().unwrap(),
+            client_ip.extract(peer, None, Some("198.51.100.1, 203.0.113.7, 10.0.0.1")),
+            "a spoofed leftmost hop is ignored"
+        );
+        assert_eq!(
+            "2001:db8::1".parse::<IpAddr>().unwrap(),
+            client_ip.extract(
+                peer,
+                Some(r#"for=198.51.100.1, for="[2001:db8::1]:4711";proto=https"#),
+                Some("203.0.113.7")
+            )
+        );
+        assert_eq!(
+            peer,
+            client_ip.extract(peer, Some("for=unknown"), None),
+            "stops at the proxy adding an unparseable hop"
+        );
+        assert_eq!(
+            "203.0.113.7".parse::<IpAddr>().unwrap(),
+            ClientIp::new().hops(2).extract(
+                peer,
+                None,
+                Some("198.51.100.1, 203.0.113.7:8080, 192.0.2.1")
+            )
+        );
+    }
+
+    #[test]
+    fn ip_prefix_bounds() {
+        let addr: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(addr, IpPrefix::new(32, 128).key(addr));
+        assert_eq!(
+            "0.0.0.0".parse::<IpAddr>().unwrap(),
+            IpPrefix::new(0, 0).key(addr)
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::IpAddr;
+
+use crate::{ClientIp, IpPrefix};
+
+/// The parts of a request keys are extracted from, borrowed from the request type of
+/// whatever HTTP crate a middleware is built on.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestParts<'a> {
+    /// Address of the connection's peer
+    pub peer: IpAddr,
+
+    /// Header names and values, in the order they were sent
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> RequestParts<'a> {
+    /// The value of the first `name` header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// The values of every `name` header joined into one list, as a header sent on
+    /// several lines means.
+    fn joined(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self
+            .headers
+            .iter()
+            .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+            .collect();
+
+        (!values.is_empty()).then(|| values.join(","))
+    }
+}
+
+/// Extracts the key a request is limited under, and what it costs.
+///
+/// Middleware takes an extractor instead of a closure, so policies are composed from
+/// the stock ones rather than reimplementing header parsing.
+///
+/// ```
+/// use std::net::IpAddr;
+/// use gcra::{ClientIp, KeyExtractor, RequestParts};
+///
+/// let extractor = ClientIp::new().hops(1).with_cost(|request| {
+///     if request.header("content-length").is_some() { 2 } else { 1 }
+/// });
+///
+/// let request = RequestParts {
+///     peer: "10.0.0.1".parse().unwrap(),
+///     headers: &[("X-Forwarded-For", "203.0.113.7")],
+/// };
+/// let (key, cost) = extractor.extract(&request).unwrap();
+/// assert_eq!("203.0.113.7".parse::<IpAddr>().unwrap(), key);
+/// assert_eq!(1, cost);
+/// ```
+pub trait KeyExtractor {
+    type Key;
+
+    /// The key and cost of `request`, `None` if it lacks what the key is made of.
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(Self::Key, u32)>;
+
+    /// Charges `cost(request)` instead of the extractor's own cost.
+    fn with_cost<F>(self, cost: F) -> WithCost<Self, F>
+    where
+        Self: Sized,
+        F: Fn(&RequestParts<'_>) -> u32,
+    {
+        WithCost {
+            extractor: self,
+            cost,
+        }
+    }
+}
+
+/// Keys requests by the network of their peer, at a cost of 1.
+///
+/// `IpPrefix::new(32, 128)` keys by the exact address.
+impl KeyExtractor for IpPrefix {
+    type Key = IpAddr;
+
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(IpAddr, u32)> {
+        Some((self.key(request.peer), 1))
+    }
+}
+
+/// Keys requests by their client address behind trusted proxies, at a cost of 1.
+impl KeyExtractor for ClientIp {
+    type Key = IpAddr;
+
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(IpAddr, u32)> {
+        let forwarded = request.joined("forwarded");
+        let x_forwarded_for = request.joined("x-forwarded-for");
+        let client = ClientIp::extract(
+            self,
+            request.peer,
+            forwarded.as_deref(),
+            x_forwarded_for.as_deref(),
+        );
+
+        Some((client, 1))
+    }
+}
+
+/// Keys requests by the value of a header, e.g. an API key, at a cost of 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderKey {
+    name: String,
+}
+
+impl HeaderKey {
+    /// Keys by the first `name` header, matched case-insensitively.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl KeyExtractor for HeaderKey {
+    type Key = String;
+
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(String, u32)> {
+        let value = request.header(&self.name)?.trim();
+        Some((value.to_string(), 1))
+    }
+}
+
+/// Keys requests by a hash of their `Authorization: Bearer` token, at a cost of 1, so
+/// tokens aren't kept around in the limiter's keys.
+///
+/// Hashes are seeded per extractor, so clients can't forge colliding tokens, and the
+/// keys of two extractors don't match.
+#[derive(Clone, Debug, Default)]
+pub struct BearerToken {
+    hasher: RandomState,
+}
+
+impl BearerToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyExtractor for BearerToken {
+    type Key = u64;
+
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(u64, u32)> {
+        let (scheme, token) = request.header("authorization")?.trim().split_once(' ')?;
+        let token = token.trim();
+        if !scheme.eq_ignore_ascii_case("bearer") || token.is_empty() {
+            return None;
+        }
+
+        Some((self.hasher.hash_one(token), 1))
+    }
+}
+
+/// An extractor charging a computed cost, see [KeyExtractor::with_cost].
+#[derive(Clone, Debug)]
+pub struct WithCost<E, F> {
+    extractor: E,
+    cost: F,
+}
+
+impl<E, F> KeyExtractor for WithCost<E, F>
+where
+    E: KeyExtractor,
+    F: Fn(&RequestParts<'_>) -> u32,
+{
+    type Key = E::Key;
+
+    fn extract(&self, request: &RequestParts<'_>) -> Option<(E::Key, u32)> {
+        let (key, _) = self.extractor.extract(request)?;
+        Some((key, (self.cost)(request)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(headers: &'a [(&'a str, &'a str)]) -> RequestParts<'a> {
+        RequestParts {
+            peer: "10.0.0.2".parse().unwrap(),
+            headers,
+        }
+    }
+
+    #[test]
+    fn ip_extractors() {
+        let headers = [
+            ("X-Forwarded-For", "198.51.100.1, 203.0.113.7"),
+            ("x-forwarded-for", "10.0.0.1"),
+        ];
+        let request = request(&headers);
+
+        assert_eq!(
+            Some(("10.0.0.0".parse().unwrap(), 1)),
+            IpPrefix::default().extract(&request)
+        );
+        assert_eq!(
+            Some(("203.0.113.7".parse().unwrap(), 1)),
+            KeyExtractor::extract(&ClientIp::new().hops(2), &request),
+            "repeated headers are one list"
+        );
+    }
+
+    #[test]
+    fn header_extractors() {
+        let api_key = HeaderKey::new("X-Api-Key");
+        assert_eq!(None, api_key.extract(&request(&[])));
+        assert_eq!(
+            Some(("abc".to_string(), 1)),
+            api_key.extract(&request(&[("x-api-key", " abc ")]))
+        );
+
+        let bearer = BearerToken::new();
+        let a = bearer.extract(&request(&[("Authorization", "Bearer abc")]));
+        let b = bearer.extract(&request(&[("authorization", "bearer  abc")]));
+        assert!(a.is_some());
+        assert_eq!(a, b);
+        assert_ne!(
+            a,
+            bearer.extract(&request(&[("Authorization", "Bearer abd")]))
+        );
+        assert_eq!(
+            None,
+            bearer.extract(&request(&[("Authorization", "Basic abc")]))
+        );
+        assert_eq!(
+            None,
+            bearer.extract(&request(&[("Authorization", "Bearer ")]))
+        );
+    }
+
+    #[test]
+    fn with_cost() {
+        let extractor = HeaderKey::new("X-Api-Key").with_cost(|request| {
+            request
+                .header("Content-Length")
+                .and_then(|len| len.parse::<u32>().ok())
+                .map_or(1, |len| len / 1024 + 1)
+        });
+
+        assert_eq!(
+            Some(("abc".to_string(), 3)),
+            extractor.extract(&request(&[
+                ("X-Api-Key", "abc"),
+                ("Content-Length", "2048")
+            ]))
+        );
+        assert_eq!(None, extractor.extract(&request(&[])), "no key, no cost");
+    }
+}
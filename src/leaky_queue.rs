@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+/// A leaky bucket used as a queue: items are accepted unconditionally and released
+/// at the rate of a [Quota], smoothing producers instead of rejecting them.
+///
+/// Releases conform to the quota, including its burst, use a quota with a burst of
+/// one to space them out evenly.
+#[derive(Debug)]
+pub struct LeakyQueue<T> {
+    quota: Quota,
+    state: State,
+    items: VecDeque<T>,
+}
+
+impl<T> LeakyQueue<T> {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            state: State::default(),
+            items: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push_back(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// When the next item can be released, or `None` if the queue is empty or the quota
+    /// will never release it, e.g. a zero quota.
+    pub fn next_release_at(&self, now: Instant) -> Option<Instant> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        match self.state.clone().check_and_modify_at(&self.quota, now, 1) {
+            Ok(()) => Some(now),
+            Err(Error::DeniedUntil(next)) => Some(next),
+            Err(Error::TimeRegressed(last)) => Some(last),
+            Err(Error::DeniedIndefinitely(_) | Error::TimeOverflow) => None,
+        }
+    }
+
+    /// Releases the next item if the quota allows it at `now`.
+    pub fn pop_at(&mut self, now: Instant) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        self.state.check_and_modify_at(&self.quota, now, 1).ok()?;
+        self.items.pop_front()
+    }
+
+    /// Releases the next item, sleeping the current thread until the quota allows it.
+    ///
+    /// Returns `None` right away if the queue is empty or the quota will never release
+    /// the next item.
+    pub fn pop_blocking(&mut self) -> Option<T> {
+        loop {
+            let now = Instant::now();
+            let next = self.next_release_at(now)?;
+            if next > now {
+                std::thread::sleep(next - now);
+            }
+
+            if let Some(item) = self.pop_at(Instant::now()) {
+                return Some(item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn leaky_queue_releases_at_rate() {
+        let quota = Quota::new(2, Duration::from_secs(2)).with_burst(1);
+        let mut queue = LeakyQueue::new(quota);
+        let now = Instant::now();
+
+        assert_eq!(None, queue.next_release_at(now));
+        for i in 0..3 {
+            queue.push(i);
+        }
+
+        assert_eq!(Some(0), queue.pop_at(now));
+        assert_eq!(
+            None,
+            queue.pop_at(now),
+            "must wait for the emission interval"
+        );
+        assert_eq!(
+            Some(now + Duration::from_secs(1)),
+            queue.next_release_at(now)
+        );
+
+        assert_eq!(Some(1), queue.pop_at(now + Duration::from_secs(1)));
+        assert_eq!(Some(2), queue.pop_at(now + Duration::from_secs(2)));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn leaky_queue_pop_blocking() {
+        let quota = Quota::new(1, Duration::from_millis(10));
+        let mut queue = LeakyQueue::new(quota);
+        queue.push("a");
+        queue.push("b");
+
+        let start = Instant::now();
+        assert_eq!(Some("a"), queue.pop_blocking());
+        assert_eq!(Some("b"), queue.pop_blocking());
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(None, queue.pop_blocking());
+    }
+
+    #[test]
+    fn leaky_queue_zero_quota() {
+        let mut queue = LeakyQueue::new(Quota::zero());
+        queue.push("a");
+
+        assert_eq!(None, queue.next_release_at(Instant::now()));
+        assert_eq!(None, queue.pop_blocking(), "must not wait forever");
+        assert_eq!(1, queue.len());
+    }
+}
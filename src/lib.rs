@@ -1,197 +1,1662 @@
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
 use std::time::{Duration, Instant};
 
+mod algorithm;
+mod backoff;
+mod bandwidth;
+mod breaker;
+mod calendar_window;
+pub mod clock;
+mod concurrency;
+mod denial_tracker;
+mod dual;
+mod fixed_window;
+#[cfg(feature = "governor")]
+mod interop;
+mod ip;
+mod key;
+mod leaky_queue;
+mod macros;
+mod monotonic;
+mod paced;
+mod pure;
+mod response;
+mod retry;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shedder;
+#[cfg(feature = "sim")]
+pub mod sim;
+mod sliding_log;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod three_color;
+mod throughput;
+mod token_bucket;
+#[cfg(feature = "trace")]
+pub mod trace;
+
+pub use algorithm::RateLimitAlgorithm;
+pub use backoff::Backoff;
+pub use bandwidth::BandwidthLimiter;
+pub use breaker::{Circuit, CircuitBreaker};
+pub use calendar_window::CalendarWindow;
+pub use clock::{Clock, CoarseClock, ManualClock, MonotonicClock};
+pub use concurrency::{AcquireError, ConcurrencyLimiter, Permit};
+pub use denial_tracker::DenialTracker;
+pub use dual::DualLimiter;
+pub use fixed_window::FixedWindow;
+#[cfg(feature = "governor")]
+pub use interop::UnsupportedQuota;
+pub use ip::{ClientIp, IpPrefix};
+pub use key::{BearerToken, HeaderKey, KeyExtractor, RequestParts, WithCost};
+pub use leaky_queue::LeakyQueue;
+#[doc(hidden)]
+pub use macros::parse_period;
+pub use macros::try_parse_period;
+pub use monotonic::{MonotonicState, Regression};
+pub use paced::PacedSender;
+pub use pure::{check, classify, Denied};
+pub use response::{RateLimitInfo, TooManyRequests};
+pub use retry::{retry_with_limit, RetryError};
+pub use shedder::Shedder;
+pub use sliding_log::SlidingLog;
+pub use three_color::{Color, DualRate, TwoRateState};
+pub use throughput::ThroughputEstimator;
+pub use token_bucket::TokenBucket;
+
 /// Defines the configuration for a GCRA rate limit.
-#[derive(Clone, Copy, Debug)]
+///
+/// Quotas are ordered by rate, ties are broken by the larger burst and then the longer
+/// period ranking higher.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub struct Quota {
     /// Amount of resources that are allowed in a given period.
     pub resource_limit: u32,
 
-    /// The length of which to allow access to the resource.
-    pub period: Duration,
+    /// The length of which to allow access to the resource.
+    pub period: Duration,
+
+    /// Incremental duration cost of a single resource check
+    pub emission_interval: Duration,
+
+    /// Amount of resources that can be consumed at once, defaults to `resource_limit`.
+    pub burst: u32,
+}
+
+impl Quota {
+    /// # Panics
+    /// If `resource_limit` is zero, which fails the build when evaluated in a const context.
+    pub const fn new(resource_limit: u32, period: Duration) -> Self {
+        assert!(
+            resource_limit > 0,
+            "resource_limit must be greater than zero"
+        );
+
+        // Duration's Div isn't const, so this is the same math spelled out.
+        let secs = period.as_secs() / resource_limit as u64;
+        let carry = period.as_secs() % resource_limit as u64;
+        let nanos = (carry * 1_000_000_000 + period.subsec_nanos() as u64) / resource_limit as u64;
+        let emission_interval = Duration::new(secs, nanos as u32);
+
+        Self {
+            resource_limit,
+            period,
+            emission_interval,
+            burst: resource_limit,
+        }
+    }
+
+    /// A quota which allows every check, whatever its cost.
+    pub const fn unlimited() -> Self {
+        Self {
+            resource_limit: u32::MAX,
+            period: Duration::ZERO,
+            emission_interval: Duration::ZERO,
+            burst: u32::MAX,
+        }
+    }
+
+    /// A quota which denies every check indefinitely.
+    pub const fn zero() -> Self {
+        Self {
+            resource_limit: 0,
+            period: Duration::ZERO,
+            emission_interval: Duration::ZERO,
+            burst: 0,
+        }
+    }
+
+    /// True if no resource can ever be consumed, like [`Quota::zero()`].
+    #[inline]
+    pub const fn is_zero(&self) -> bool {
+        self.resource_limit == 0 || self.burst == 0
+    }
+
+    /// True if resources are emitted instantly, like [`Quota::unlimited()`].
+    #[inline]
+    pub const fn is_unlimited(&self) -> bool {
+        !self.is_zero() && self.emission_interval.is_zero()
+    }
+
+    /// Sets the amount of resources that can be consumed at once, without changing the rate.
+    ///
+    /// # Panics
+    /// If `burst` is zero, which fails the build when evaluated in a const context.
+    pub const fn with_burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "burst must be greater than zero");
+
+        self.burst = burst;
+        self
+    }
+
+    /// Strict pacing mode: disallows bursts, so consecutive checks are spaced out by at
+    /// least `emission_interval`, e.g. `Quota::new(10, Duration::from_secs(1)).paced()`
+    /// allows exactly one check per 100ms.
+    ///
+    /// Same as `with_burst(1)`, so checks costing more than one will never succeed.
+    pub const fn paced(self) -> Self {
+        self.with_burst(1)
+    }
+
+    /// Scales the rate and the burst by `factor`, e.g. `0.5` for a degraded mode running
+    /// at half the rate. The burst is rounded, but kept at one resource at least.
+    ///
+    /// Factors which aren't positive, or too small for the period to be represented, give
+    /// [`Quota::zero()`], factors so large that the period rounds to zero give
+    /// [`Quota::unlimited()`].
+    pub fn scale(&self, factor: f64) -> Self {
+        if self.is_zero() || self.is_unlimited() {
+            return *self;
+        }
+        if factor.is_nan() || factor <= 0.0 {
+            return Self::zero();
+        }
+
+        let period = match Duration::try_from_secs_f64(self.period.as_secs_f64() / factor) {
+            Ok(period) if !period.is_zero() => period,
+            Ok(_) => return Self::unlimited(),
+            Err(_) => return Self::zero(),
+        };
+        let burst = (self.burst as f64 * factor)
+            .round()
+            .clamp(1.0, u32::MAX as f64) as u32;
+        Self::new(self.resource_limit, period).with_burst(burst)
+    }
+
+    /// The strictest combination of both quotas: the lower rate and the smaller burst.
+    ///
+    /// Unlike [Ord::min], which picks one of the quotas whole, this combines them, so a
+    /// check passing the result passes both.
+    pub fn strictest(&self, other: &Quota) -> Self {
+        if self.is_zero() || other.is_unlimited() {
+            return *self;
+        }
+        if other.is_zero() || self.is_unlimited() {
+            return *other;
+        }
+
+        let slower = if self.emission_interval >= other.emission_interval {
+            self
+        } else {
+            other
+        };
+        Self::new(slower.resource_limit, slower.period).with_burst(self.burst.min(other.burst))
+    }
+
+    /// Divides the quota into `n` equal shares, e.g. node-local shares of a global quota.
+    ///
+    /// The burst is divided too, rounding down so the shares don't allow more than the
+    /// whole at once, but kept at one resource at least. When the period of a share
+    /// would overflow, the limit is divided instead, rounding down, and a share slower
+    /// than that is [zero](Quota::zero).
+    ///
+    /// # Panics
+    /// If `n` is zero.
+    pub fn split(&self, n: u32) -> Self {
+        assert!(n > 0, "n must be greater than zero");
+        if self.is_zero() || self.is_unlimited() {
+            return *self;
+        }
+
+        let share = match self.period.checked_mul(n) {
+            Some(period) => Self::new(self.resource_limit, period),
+            None if self.resource_limit >= n => Self::new(self.resource_limit / n, self.period),
+            None => return Self::zero(),
+        };
+        share.with_burst((self.burst / n).max(1))
+    }
+
+    /// Given a `cost`, calculates the increment interval, saturating at [Duration::MAX].
+    #[inline]
+    pub fn increment_interval(&self, cost: u32) -> Duration {
+        self.emission_interval.saturating_mul(cost)
+    }
+
+    /// Given a `cost`, calculates the increment interval, or `None` on overflow.
+    #[inline]
+    pub fn checked_increment_interval(&self, cost: u32) -> Option<Duration> {
+        self.emission_interval.checked_mul(cost)
+    }
+
+    /// How far ahead of the arrival time the TAT may run, which is the duration
+    /// `burst` resources take to be emitted.
+    #[inline]
+    pub const fn delay_variation_tolerance(&self) -> Duration {
+        if self.burst == self.resource_limit || self.is_zero() || self.is_unlimited() {
+            return self.period;
+        }
+
+        // saturates, since a burst larger than the limit may not fit in a Duration
+        let nanos = self.period.as_nanos() * self.burst as u128 / self.resource_limit as u128;
+        if nanos / 1_000_000_000 > u64::MAX as u128 {
+            return Duration::MAX;
+        }
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+impl Display for Quota {
+    /// Renders like `100 per 1s (burst 100)`, or `unlimited` and `zero` for the sentinels.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return fmt.write_str("zero");
+        }
+        if self.is_unlimited() {
+            return fmt.write_str("unlimited");
+        }
+
+        write!(
+            fmt,
+            "{} per {:?} (burst {})",
+            self.resource_limit, self.period, self.burst
+        )
+    }
+}
+
+impl PartialOrd for Quota {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Quota {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare resource_limit/period without losing precision:
+        //   a.limit / a.period <=> b.limit / b.period
+        let lhs = self.resource_limit as u128 * other.period.as_nanos();
+        let rhs = other.resource_limit as u128 * self.period.as_nanos();
+
+        // zero quotas have no rate at all, rank them lowest
+        (!self.is_zero())
+            .cmp(&!other.is_zero())
+            .then_with(|| lhs.cmp(&rhs))
+            .then_with(|| self.burst.cmp(&other.burst))
+            .then_with(|| self.period.cmp(&other.period))
+            .then_with(|| self.resource_limit.cmp(&other.resource_limit))
+            .then_with(|| self.emission_interval.cmp(&other.emission_interval))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Cost of the increment exceeds the rate limit and will never succeed
+    DeniedIndefinitely(u32),
+
+    /// Limited request until after the [Instant]
+    DeniedUntil(Instant),
+
+    /// Arrived before the [Instant] of a previous arrival, see [MonotonicState]
+    TimeRegressed(Instant),
+
+    /// Computing the TAT overflowed what an [Instant] can represent
+    TimeOverflow,
+}
+
+impl Error {
+    /// Delays [Error::DeniedUntil] by a random duration of up to `max_jitter`, so clients
+    /// told to retry at the same time don't all come back at the same instant.
+    ///
+    /// Only ever delays the retry, so it never falls before the time the limiter allows.
+    pub fn with_jitter(self, max_jitter: Duration) -> Self {
+        self.with_jitter_ratio(max_jitter, random_ratio())
+    }
+
+    /// Same as [`with_jitter()`](Error::with_jitter), delaying by `ratio * max_jitter`,
+    /// where `ratio` is clamped to `[0, 1]` and NaN counts as `0`. A jitter delaying past
+    /// what an [Instant] can represent leaves the retry time as it is.
+    pub fn with_jitter_ratio(self, max_jitter: Duration, ratio: f64) -> Self {
+        let next = match self {
+            Error::DeniedUntil(next) => next,
+            err => return err,
+        };
+
+        let ratio = if ratio.is_nan() {
+            0.0
+        } else {
+            ratio.clamp(0.0, 1.0)
+        };
+        let jitter = Duration::try_from_secs_f64(max_jitter.as_secs_f64() * ratio)
+            .map_or(max_jitter, |jitter| jitter.min(max_jitter));
+        Error::DeniedUntil(next.checked_add(jitter).unwrap_or(next))
+    }
+}
+
+#[inline]
+fn checked_add(instant: Instant, duration: Duration) -> Result<Instant, Error> {
+    instant.checked_add(duration).ok_or(Error::TimeOverflow)
+}
+
+/// A random number in `[0, 1)`, good enough to spread out retries but not much more.
+fn random_ratio() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hash, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    // RandomState is seeded randomly per thread, and its keys change with every instance,
+    // the time and a call counter are mixed in on top
+    let mut hasher = RandomState::new().build_hasher();
+    Instant::now().hash(&mut hasher);
+    hasher.write_u64(CALLS.fetch_add(1, Ordering::Relaxed));
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::DeniedIndefinitely(cost) => {
+                write!(
+                    fmt,
+                    "cost of the increment {} exceeds the rate limit and will never succeed",
+                    cost
+                )
+            }
+            Error::DeniedUntil(next) => write!(fmt, "denied until {:?}", next),
+            Error::TimeRegressed(last) => {
+                write!(fmt, "arrived before the previous arrival at {:?}", last)
+            }
+            Error::TimeOverflow => write!(fmt, "computing the TAT overflowed"),
+        }
+    }
+}
+
+/// Resources booked by a check, see [`State::book_at()`].
+///
+/// The booking spans the part of the TAT timeline the resources were added to, which
+/// leaks away as time passes. It is consumed when cancelled, so it can only be
+/// refunded once.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Booking {
+    start: Instant,
+    end: Instant,
+}
+
+impl Booking {
+    /// Where the resources were added to the TAT timeline.
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// The TAT after the resources were added, i.e. when they fully leaked.
+    pub fn end(&self) -> Instant {
+        self.end
+    }
+}
+
+/// A copy of a [State] taken at a given time, see [`State::snapshot()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    pub tat: Option<Instant>,
+    pub taken_at: Instant,
+}
+
+impl Snapshot {
+    /// How far the TAT moved forward between this snapshot and a later `other`, i.e.
+    /// the capacity consumed in between, as a duration.
+    ///
+    /// A TAT which already leaked by the time of either snapshot counts from then, so
+    /// idle time doesn't count as consumption. Reverts yield [Duration::ZERO].
+    pub fn diff(&self, other: &Snapshot) -> Duration {
+        let taken_at = self.taken_at.max(other.taken_at);
+        let from = self.tat.map_or(taken_at, |tat| tat.max(taken_at));
+        let to = other.tat.map_or(from, |tat| tat.max(from));
+        to - from
+    }
+}
+
+/// The outcome of a check with everything needed for responses, logs and metrics, see
+/// [`State::check_decision_at()`].
+#[derive(Debug)]
+pub enum Decision {
+    /// The check passed
+    Allowed {
+        /// Resources still available
+        remaining: u32,
+
+        /// Time until the whole burst is available again
+        reset_after: Duration,
+    },
+
+    /// The check would pass after waiting
+    Throttled {
+        /// Time until a retry may pass
+        retry_after: Duration,
+    },
+
+    /// The check can't pass by waiting
+    Blocked { reason: Error },
+}
+
+impl Decision {
+    #[inline]
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allowed { .. })
+    }
+}
+
+/// Holds the minimum amount of state necessary to implement a GCRA leaky buckets.
+/// Refer to: [understanding GCRA](https://blog.ian.stapletoncordas.co/2018/12/understanding-generic-cell-rate-limiting.html)
+#[derive(Clone, Default, Debug)]
+pub struct State {
+    /// GCRA's Theoretical Arrival Time (**TAT**)
+    /// An unset value signals a new state
+    pub tat: Option<Instant>,
+}
+
+impl State {
+    /// A new state starting with only `fill` of the burst available at `now`, from `0.0`
+    /// for an empty bucket to `1.0` for a full one like [`State::default()`]. A NaN fill
+    /// counts as empty.
+    ///
+    /// Useful for abuse-sensitive keys, e.g. logins, which shouldn't get a full burst
+    /// right away.
+    pub fn with_fill(rate_limit: &Quota, now: Instant, fill: f64) -> Self {
+        let mut state = Self::default();
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return state;
+        }
+
+        let fill = if fill.is_nan() {
+            0.0
+        } else {
+            fill.clamp(0.0, 1.0)
+        };
+        let consumed = rate_limit.delay_variation_tolerance().mul_f64(1.0 - fill);
+        if !consumed.is_zero() {
+            state.tat = now.checked_add(consumed);
+        }
+        state
+    }
+
+    /// Check if we are allowed to proceed. If so updated our internal state and return true.
+    ///
+    /// Simply passes the current Instant to [`check_and_modify_at()`]
+    #[inline]
+    pub fn check_and_modify(&mut self, rate_limit: &Quota, cost: u32) -> Result<(), Error> {
+        self.check_and_modify_at(rate_limit, Instant::now(), cost)
+    }
+
+    /// Check if we are allowed to proceed at the given arrival time.
+    /// If so updated our internal state and return true.
+    /// Explanation of GCRA can be found [here](https://blog.ian.stapletoncordas.co/2018/12/understanding-generic-cell-rate-limiting.html)
+    ///
+    /// A `cost` of zero is a free [`probe_at()`](State::probe_at), which never modifies
+    /// our state.
+    ///
+    /// # Returns
+    /// If denied, will return an [Result::Err] where the value is the next allowed timestamp.
+    pub fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        if rate_limit.is_zero() {
+            return Err(Error::DeniedIndefinitely(cost));
+        }
+        if rate_limit.is_unlimited() {
+            return Ok(());
+        }
+        if cost == 0 {
+            return self
+                .probe_at(rate_limit, arrived_at)
+                .map_err(|err| match err {
+                    Error::DeniedIndefinitely(_) => Error::DeniedIndefinitely(cost),
+                    err => err,
+                });
+        }
+
+        // An overflowing increment exceeds any tolerance
+        let delay_variation_tolerance = rate_limit.delay_variation_tolerance();
+        let increment_interval = match rate_limit.checked_increment_interval(cost) {
+            Some(increment_interval) if increment_interval <= delay_variation_tolerance => {
+                increment_interval
+            }
+            _ => return Err(Error::DeniedIndefinitely(cost)),
+        };
+
+        let tat = match self.tat {
+            Some(tat) => tat,
+            None => {
+                // First ever request. Allow passage and update self.
+                self.tat = Some(checked_add(arrived_at, increment_interval)?);
+                return Ok(());
+            }
+        };
+
+        // We had a previous request
+        if tat < arrived_at {
+            // prev request was really old
+            let new_tat = std::cmp::max(tat, arrived_at);
+            self.tat = Some(checked_add(new_tat, increment_interval)?);
+        } else {
+            // prev request was recent and there's a possibility that we've reached the limit
+            let new_tat = checked_add(tat, increment_interval)?;
+
+            // An underflow means next_allowed_at is earlier than any Instant, so allowed
+            match new_tat.checked_sub(delay_variation_tolerance) {
+                Some(next_allowed_at) if next_allowed_at > arrived_at => {
+                    // Denied, must wait until next_allowed_at
+                    return Err(Error::DeniedUntil(next_allowed_at));
+                }
+                _ => self.tat = Some(new_tat),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`check_and_modify()`](State::check_and_modify), returning a [Decision].
+    ///
+    /// Simply passes the current Instant to [`check_decision_at()`](State::check_decision_at)
+    #[inline]
+    pub fn check_decision(&mut self, rate_limit: &Quota, cost: u32) -> Decision {
+        self.check_decision_at(rate_limit, Instant::now(), cost)
+    }
+
+    /// Same as [`check_and_modify_at()`](State::check_and_modify_at), returning a [Decision].
+    pub fn check_decision_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Decision {
+        match self.check_and_modify_at(rate_limit, arrived_at, cost) {
+            Ok(()) => Decision::Allowed {
+                remaining: self.remaining_resources(rate_limit, arrived_at),
+                reset_after: self
+                    .expires_at(rate_limit)
+                    .map_or(Duration::ZERO, |expires_at| {
+                        expires_at.saturating_duration_since(arrived_at)
+                    }),
+            },
+            Err(Error::DeniedUntil(next_allowed_at)) => Decision::Throttled {
+                retry_after: next_allowed_at.saturating_duration_since(arrived_at),
+            },
+            Err(reason) => Decision::Blocked { reason },
+        }
+    }
+
+    /// Check if the bucket is non-empty, i.e. a single resource would be allowed at the
+    /// given arrival time, without modifying our state.
+    ///
+    /// Simply passes the current Instant to [`probe_at()`](State::probe_at)
+    #[inline]
+    pub fn probe(&self, rate_limit: &Quota) -> Result<(), Error> {
+        self.probe_at(rate_limit, Instant::now())
+    }
+
+    /// Check if the bucket is non-empty, i.e. a single resource would be allowed at the
+    /// given arrival time, without modifying our state.
+    ///
+    /// # Returns
+    /// If empty, will return an [Result::Err] where the value is the time a resource
+    /// becomes available.
+    pub fn probe_at(&self, rate_limit: &Quota, arrived_at: Instant) -> Result<(), Error> {
+        self.clone().check_and_modify_at(rate_limit, arrived_at, 1)
+    }
+
+    /// How long the caller would have to wait from `now` for a check of `cost` to pass,
+    /// [Duration::ZERO] if it would pass right away, without modifying our state.
+    ///
+    /// # Returns
+    /// [Error::DeniedIndefinitely] if the check can never pass.
+    pub fn estimate_wait(
+        &self,
+        rate_limit: &Quota,
+        now: Instant,
+        cost: u32,
+    ) -> Result<Duration, Error> {
+        match self.clone().check_and_modify_at(rate_limit, now, cost) {
+            Ok(()) => Ok(Duration::ZERO),
+            Err(Error::DeniedUntil(next_allowed_at)) => Ok(next_allowed_at - now),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// How long from `now` until `n` resources are available, so batch jobs can sleep
+    /// once instead of polling. Unlike [`estimate_wait()`](State::estimate_wait), zero
+    /// resources are always available.
+    ///
+    /// # Returns
+    /// [Error::DeniedIndefinitely] if `n` resources are never available at once.
+    pub fn time_until_n_available(
+        &self,
+        rate_limit: &Quota,
+        now: Instant,
+        n: u32,
+    ) -> Result<Duration, Error> {
+        if n == 0 {
+            return Ok(Duration::ZERO);
+        }
+
+        self.estimate_wait(rate_limit, now, n)
+    }
+
+    /// Same as [`check_and_modify_at()`](State::check_and_modify_at), but starts denying
+    /// a growing fraction of the checks once utilization goes beyond `threshold`, RED-style.
+    ///
+    /// Utilization is the share of the burst consumed after the check. Past `threshold`,
+    /// checks are denied with a probability growing linearly from 0 to 1 at full utilization.
+    /// This smooths the cliff where everything is allowed and then everything is denied.
+    ///
+    /// # Returns
+    /// Early denials are [Error::DeniedUntil] the time utilization falls back to `threshold`.
+    pub fn check_and_modify_early_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+        threshold: f64,
+    ) -> Result<(), Error> {
+        self.check_and_modify_early_sampled_at(
+            rate_limit,
+            arrived_at,
+            cost,
+            threshold,
+            random_ratio(),
+        )
+    }
+
+    /// Same as [`check_and_modify_early_at()`](State::check_and_modify_early_at), with the
+    /// random `sample` in `[0, 1)` supplied by the caller.
+    pub fn check_and_modify_early_sampled_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+        threshold: f64,
+        sample: f64,
+    ) -> Result<(), Error> {
+        let mut next = self.clone();
+        next.check_and_modify_at(rate_limit, arrived_at, cost)?;
+
+        let threshold = threshold.clamp(0.0, 1.0);
+        let utilization = next.pressure(rate_limit, arrived_at);
+        if utilization > threshold {
+            let drop_probability = (utilization - threshold) / (1.0 - threshold);
+            if sample < drop_probability {
+                if let Some(eases_at) = next.pressure_eases_at(rate_limit, threshold) {
+                    return Err(Error::DeniedUntil(eases_at));
+                }
+            }
+        }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Check if we are allowed to proceed now, or at the latest by `deadline`.
+    /// If so, books the resources at the time we are allowed to proceed.
+    ///
+    /// # Returns
+    /// When the caller may proceed, `arrived_at` if right away, otherwise it has to wait
+    /// until then. Denials which wouldn't pass by `deadline` leave the state unchanged.
+    pub fn check_within(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+        deadline: Instant,
+    ) -> Result<Instant, Error> {
+        match self.check_and_modify_at(rate_limit, arrived_at, cost) {
+            Ok(()) => Ok(arrived_at),
+            Err(Error::DeniedUntil(next_allowed_at)) if next_allowed_at <= deadline => {
+                self.check_and_modify_at(rate_limit, next_allowed_at, cost)?;
+                Ok(next_allowed_at)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reverts rate_limit by cost, and updated our internal state.
+    ///
+    /// Simply passes the current Instant to [`revert_at()`]
+    #[inline]
+    pub fn revert(&mut self, rate_limit: &Quota, cost: u32) {
+        let arrived_at = Instant::now();
+        self.revert_at(rate_limit, arrived_at, cost)
+    }
+
+    /// Reverts rate_limit by cost, and updated our internal state.
+    ///
+    /// Subtracts the incremental cost from the TAT, bounded by what is still consumed
+    /// at `arrived_at`, so reverting never grants more than a full burst. Resources which
+    /// leaked since being consumed can't be told apart from more recent ones though, use
+    /// [`book_at()`](State::book_at) and [`cancel_at()`](State::cancel_at) to only refund
+    /// what a given check still holds.
+    pub fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        let increment_interval = rate_limit.increment_interval(cost);
+
+        let tat = match self.tat {
+            Some(tat) => tat,
+            None => {
+                // First ever request. Nothing to do.
+                return;
+            }
+        };
+
+        // We had a previous request
+        if tat < arrived_at {
+            // Reset state: prev request was really old
+            self.tat = None;
+        } else {
+            // prev request was recent, refund no more than what is consumed
+            self.refund(arrived_at, increment_interval);
+        }
+    }
+
+    /// Same as [`check_and_modify_at()`](State::check_and_modify_at), returning the
+    /// [Booking] of the resources, so they can be accurately cancelled later.
+    pub fn book_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<Booking, Error> {
+        let start = self.tat.map_or(arrived_at, |tat| tat.max(arrived_at));
+        self.check_and_modify_at(rate_limit, arrived_at, cost)?;
+
+        // probes and unlimited quotas book nothing
+        let end = match self.tat {
+            Some(tat) if cost > 0 && !rate_limit.is_unlimited() => tat,
+            _ => start,
+        };
+        Ok(Booking { start, end })
+    }
+
+    /// Cancels a [Booking], refunding only the part of it which hasn't leaked yet at `now`.
+    ///
+    /// Unlike [`revert_at()`](State::revert_at), this can't refund capacity consumed by
+    /// later checks when the booking itself has already leaked.
+    ///
+    /// The booking is taken by value, so the same resources can't be refunded twice:
+    ///
+    /// ```compile_fail
+    /// # use std::time::{Duration, Instant};
+    /// # use gcra::{Quota, State};
+    /// let quota = Quota::new(5, Duration::from_secs(5));
+    /// let mut state = State::default();
+    /// let now = Instant::now();
+    ///
+    /// let booking = state.book_at(&quota, now, 2).unwrap();
+    /// state.cancel_at(booking, now);
+    /// state.cancel_at(booking, now);
+    /// ```
+    pub fn cancel_at(&mut self, booking: Booking, now: Instant) {
+        let outstanding = booking
+            .end
+            .saturating_duration_since(booking.start.max(now));
+        if !outstanding.is_zero() {
+            self.refund(now, outstanding);
+        }
+    }
+
+    /// Moves the TAT back by `refund`, but not before `now`.
+    fn refund(&mut self, now: Instant, refund: Duration) {
+        if let Some(tat) = self.tat {
+            let reverted = tat.checked_sub(refund).unwrap_or(now);
+            self.tat = Some(reverted.max(now));
+        }
+    }
+
+    pub fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        if rate_limit.is_zero() {
+            return 0;
+        }
+        if rate_limit.is_unlimited() {
+            return rate_limit.burst;
+        }
+
+        let time_to_tat = match self.tat.and_then(|tat| tat.checked_duration_since(now)) {
+            Some(duration_until) => duration_until.as_secs_f32(),
+            None => return rate_limit.burst,
+        };
+
+        // Logically this makes more sense as:
+        //   consumed_resources = time_to_tat * (resource_limit/period)
+        // but we run it this way because of Duration's arithmetic functions
+        let consumed_resources =
+            (time_to_tat * rate_limit.resource_limit as f32) / rate_limit.period.as_secs_f32();
+        rate_limit
+            .burst
+            .saturating_sub(consumed_resources.ceil() as u32)
+    }
+
+    /// Exhausts the whole burst, cutting the caller off until it leaks again.
+    ///
+    /// Simply passes the current Instant to [`consume_all_at()`](State::consume_all_at)
+    #[inline]
+    pub fn consume_all(&mut self, rate_limit: &Quota) {
+        self.consume_all_at(rate_limit, Instant::now())
+    }
+
+    /// Exhausts the whole burst at `now`, so the next resource is only allowed one
+    /// emission interval later. A state already denied for longer is left as is.
+    pub fn consume_all_at(&mut self, rate_limit: &Quota, now: Instant) {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return;
+        }
+
+        if let Some(exhausted) = now.checked_add(rate_limit.delay_variation_tolerance()) {
+            self.tat = self.tat.max(Some(exhausted));
+        }
+    }
+
+    /// Carries the state over to a quota changed at runtime, from `old` to `new`.
+    ///
+    /// Resources consumed under `old` at `now` stay consumed under `new`, up to its
+    /// burst, so the change neither refunds what was used nor denies for longer than
+    /// `new` ever would. Keeping the TAT as is instead would count it at the old rate.
+    pub fn rescale(&mut self, old: &Quota, new: &Quota, now: Instant) {
+        let time_to_tat = match self.tat.and_then(|tat| tat.checked_duration_since(now)) {
+            Some(time_to_tat) if !old.is_zero() && !old.is_unlimited() => time_to_tat,
+            _ => {
+                self.tat = None;
+                return;
+            }
+        };
+        if new.is_zero() || new.is_unlimited() {
+            self.tat = None;
+            return;
+        }
+
+        let consumed = time_to_tat.as_secs_f64() / old.emission_interval.as_secs_f64();
+        let tolerance = new.delay_variation_tolerance();
+        let time_to_tat =
+            Duration::try_from_secs_f64(new.emission_interval.as_secs_f64() * consumed)
+                .map_or(tolerance, |time_to_tat| time_to_tat.min(tolerance));
+        self.tat = now.checked_add(time_to_tat);
+    }
+
+    /// Forgets every check, as if the state was new.
+    pub fn reset(&mut self) {
+        self.tat = None;
+    }
+
+    /// True if the state is as good as new at `now`, i.e. the TAT is at or before `now`,
+    /// so keyed stores can drop it.
+    pub fn is_expired(&self, rate_limit: &Quota, now: Instant) -> bool {
+        match self.expires_at(rate_limit) {
+            Some(expires_at) => expires_at <= now,
+            None => true,
+        }
+    }
+
+    /// When the state becomes as good as new, `None` if it already is whenever checked.
+    pub fn expires_at(&self, rate_limit: &Quota) -> Option<Instant> {
+        if rate_limit.is_unlimited() {
+            // checks never consume anything
+            return None;
+        }
+
+        self.tat
+    }
+
+    /// Resources available right now.
+    ///
+    /// Simply passes the current Instant to [`remaining_resources()`](State::remaining_resources)
+    #[inline]
+    pub fn remaining(&self, rate_limit: &Quota) -> u32 {
+        self.remaining_resources(rate_limit, Instant::now())
+    }
+
+    /// Resources available right now, including the fraction of the next one.
+    ///
+    /// Simply passes the current Instant to [`remaining_f64_at()`](State::remaining_f64_at)
+    #[inline]
+    pub fn remaining_f64(&self, rate_limit: &Quota) -> f64 {
+        self.remaining_f64_at(rate_limit, Instant::now())
+    }
+
+    /// Resources available at `now`, including the fraction of the next one which
+    /// [`remaining_resources()`](State::remaining_resources) rounds down.
+    pub fn remaining_f64_at(&self, rate_limit: &Quota, now: Instant) -> f64 {
+        if rate_limit.is_zero() {
+            return 0.0;
+        }
+        if rate_limit.is_unlimited() {
+            return rate_limit.burst as f64;
+        }
+
+        let time_to_tat = match self.tat.and_then(|tat| tat.checked_duration_since(now)) {
+            Some(duration_until) => duration_until.as_secs_f64(),
+            None => return rate_limit.burst as f64,
+        };
+
+        let consumed_resources =
+            (time_to_tat * rate_limit.resource_limit as f64) / rate_limit.period.as_secs_f64();
+        (rate_limit.burst as f64 - consumed_resources).max(0.0)
+    }
+
+    /// Merges the state of the same key from another shard or replica, keeping the
+    /// later TAT, so the merged state is as strict as the stricter of the two.
+    pub fn merge_max(&mut self, other: &State) {
+        self.tat = self.tat.max(other.tat);
+    }
+
+    /// Merges the state of the same key from another shard or replica, moving the TAT
+    /// `weight` of the way towards the other one, e.g. `0.5` averages both.
+    ///
+    /// TATs at or before `now` count as `now`, so idle time doesn't skew the result.
+    pub fn merge_weighted(&mut self, other: &State, now: Instant, weight: f64) {
+        let ours = self.tat.map_or(now, |tat| tat.max(now));
+        let theirs = other.tat.map_or(now, |tat| tat.max(now));
+
+        let weight = weight.clamp(0.0, 1.0);
+        let merged = if theirs >= ours {
+            ours + (theirs - ours).mul_f64(weight)
+        } else {
+            ours - (ours - theirs).mul_f64(weight)
+        };
+
+        self.tat = if merged > now { Some(merged) } else { None };
+    }
+
+    /// Takes a [Snapshot] of the state.
+    ///
+    /// Simply passes the current Instant to [`snapshot_at()`](State::snapshot_at)
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    /// Takes a [Snapshot] of the state at `now`.
+    pub fn snapshot_at(&self, now: Instant) -> Snapshot {
+        Snapshot {
+            tat: self.tat,
+            taken_at: now,
+        }
+    }
+
+    /// Share of the burst consumed at `now`, from `0.0` when idle to `1.0` when exhausted.
+    pub fn pressure(&self, rate_limit: &Quota, now: Instant) -> f64 {
+        if rate_limit.is_zero() {
+            return 1.0;
+        }
+        if rate_limit.is_unlimited() {
+            return 0.0;
+        }
+
+        let time_to_tat = match self.tat.and_then(|tat| tat.checked_duration_since(now)) {
+            Some(duration_until) => duration_until.as_secs_f64(),
+            None => return 0.0,
+        };
+
+        let tolerance = rate_limit.delay_variation_tolerance().as_secs_f64();
+        (time_to_tat / tolerance).min(1.0)
+    }
+
+    /// When [`pressure()`](State::pressure) falls to `pressure`, if it can ever be
+    /// above it.
+    pub(crate) fn pressure_eases_at(&self, rate_limit: &Quota, pressure: f64) -> Option<Instant> {
+        if rate_limit.is_zero() || rate_limit.is_unlimited() {
+            return None;
+        }
+
+        let tolerance = rate_limit.delay_variation_tolerance();
+        self.tat
+            .and_then(|tat| tat.checked_sub(tolerance.mul_f64(pressure.clamp(0.0, 1.0))))
+    }
+
+    /// Encodes the TAT as a number of `resolution` ticks since `epoch`, so it can be
+    /// stored in fewer bits or serialized.
+    ///
+    /// The TAT is rounded up to the next tick, so a decoded state is never more permissive
+    /// than the original one. An unset TAT, or one at or before `epoch`, encodes as `0`,
+    /// which decodes to a new state. `epoch` must not be later than any arrival time
+    /// the decoded state is checked with.
+    pub fn to_ticks(&self, epoch: Instant, resolution: Duration) -> u64 {
+        let since_epoch = match self.tat.and_then(|tat| tat.checked_duration_since(epoch)) {
+            Some(since_epoch) => since_epoch.as_nanos(),
+            None => return 0,
+        };
+
+        // saturates, so an unrepresentable TAT fails to decode rather than wrapping
+        let resolution = resolution.as_nanos().max(1);
+        u64::try_from(since_epoch.div_ceil(resolution)).unwrap_or(u64::MAX)
+    }
+
+    /// Decodes a state encoded by [`to_ticks()`](State::to_ticks) with the same `epoch` and `resolution`.
+    ///
+    /// # Returns
+    /// `None` if the TAT is past what an [Instant] can represent.
+    pub fn from_ticks(ticks: u64, epoch: Instant, resolution: Duration) -> Option<Self> {
+        if ticks == 0 {
+            return Some(Self::default());
+        }
+
+        let since_epoch = resolution.as_nanos().max(1).checked_mul(ticks as u128)?;
+        let secs = u64::try_from(since_epoch / 1_000_000_000).ok()?;
+        let since_epoch = Duration::new(secs, (since_epoch % 1_000_000_000) as u32);
+        Some(Self {
+            tat: Some(epoch.checked_add(since_epoch)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn rate_limit_emission_interval() {
+        let rate_limit = Quota::new(10, Duration::from_secs(20));
+        assert_eq!(Duration::from_secs(2), rate_limit.emission_interval)
+    }
+
+    #[test]
+    fn quota_eq_and_hash() {
+        use std::collections::HashSet;
+
+        let a = Quota::new(10, Duration::from_secs(1));
+        let b = Quota::new(10, Duration::from_secs(1));
+        assert_eq!(a, b);
+
+        let set = HashSet::from([a, b, Quota::new(20, Duration::from_secs(2))]);
+        assert_eq!(2, set.len());
+    }
+
+    #[test]
+    fn quota_ordered_by_rate() {
+        let slow = Quota::new(1, Duration::from_secs(1));
+        let fast = Quota::new(100, Duration::from_secs(10));
+        assert!(slow < fast);
+
+        // same rate, the larger burst ranks higher
+        let small_burst = Quota::new(10, Duration::from_secs(1));
+        let large_burst = Quota::new(20, Duration::from_secs(2));
+        assert!(small_burst < large_burst);
+        assert_ne!(small_burst, large_burst);
+    }
+
+    #[test]
+    fn quota_display() {
+        assert_eq!(
+            "100 per 1s (burst 100)",
+            Quota::new(100, Duration::from_secs(1)).to_string()
+        );
+        assert_eq!(
+            "5 per 500ms (burst 5)",
+            Quota::new(5, Duration::from_millis(500)).to_string()
+        );
+    }
+
+    #[test]
+    fn tolerance_bounds() {
+        // a tolerance of 2^64 seconds saturates rather than wrapping to zero
+        let quota = Quota::new(1, Duration::from_secs(1 << 62)).with_burst(4);
+        assert_eq!(Duration::MAX, quota.delay_variation_tolerance());
+        assert!(State::default()
+            .check_and_modify_at(&quota, Instant::now(), 1)
+            .is_ok());
+
+        assert_eq!(
+            Duration::ZERO,
+            Quota::zero().with_burst(4).delay_variation_tolerance()
+        );
+        assert_eq!(
+            Duration::ZERO,
+            Quota::unlimited().with_burst(4).delay_variation_tolerance()
+        );
+    }
+
+    #[test]
+    fn quota_with_burst() {
+        let quota = Quota::new(10, Duration::from_secs(10)).with_burst(2);
+        assert_eq!(Duration::from_secs(2), quota.delay_variation_tolerance());
+        assert_eq!("10 per 10s (burst 2)", quota.to_string());
+
+        let mut gcra = State::default();
+        let now = Instant::now();
+        for i in 0..2 {
+            assert!(
+                gcra.check_and_modify_at(&quota, now, 1).is_ok(),
+                "request #{} should pass",
+                i + 1
+            );
+        }
+        assert!(
+            matches!(gcra.check_and_modify_at(&quota, now, 1), Err(Error::DeniedUntil(next)) if next == now + quota.emission_interval),
+            "burst is exhausted"
+        );
+        assert!(matches!(
+            gcra.check_and_modify_at(&quota, now, 3),
+            Err(Error::DeniedIndefinitely(3))
+        ));
+        assert_eq!(2, gcra.remaining_resources(&quota, now + quota.period));
+    }
+
+    #[test]
+    fn quota_arithmetic() {
+        let quota = Quota::new(100, Duration::from_secs(10));
+
+        let half = quota.scale(0.5);
+        assert_eq!(Duration::from_millis(200), half.emission_interval);
+        assert_eq!(50, half.burst);
+        assert!(quota.scale(0.0).is_zero());
+        assert!(Quota::unlimited().scale(0.5).is_unlimited());
+
+        let share = quota.split(4);
+        assert_eq!(Duration::from_millis(400), share.emission_interval);
+        assert_eq!(25, share.burst);
+        assert_eq!(1, Quota::new(2, Duration::from_secs(1)).split(4).burst);
+
+        // shares of long periods divide the limit rather than saturating the period
+        let long = Quota::new(10, Duration::from_secs(u64::MAX / 2));
+        let share = long.split(4);
+        assert_eq!(Quota::new(2, long.period).with_burst(2), share);
+        assert!(share.emission_interval >= long.emission_interval * 4);
+        assert!(long.split(20).is_zero());
+
+        let other = Quota::new(20, Duration::from_secs(1)).with_burst(5);
+        let strictest = quota.strictest(&other);
+        assert_eq!(quota.emission_interval, strictest.emission_interval);
+        assert_eq!(5, strictest.burst);
+        assert_eq!(other, other.strictest(&Quota::unlimited()));
+        assert!(other.strictest(&Quota::zero()).is_zero());
+    }
+
+    #[test]
+    fn quota_paced() {
+        let quota = Quota::new(10, Duration::from_secs(1)).paced();
+        assert_eq!(quota.emission_interval, quota.delay_variation_tolerance());
+
+        let mut gcra = State::default();
+        let now = Instant::now();
+        assert!(gcra.check_and_modify_at(&quota, now, 1).is_ok());
+        assert!(
+            matches!(
+                gcra.check_and_modify_at(&quota, now + Duration::from_millis(99), 1),
+                Err(Error::DeniedUntil(next)) if next == now + Duration::from_millis(100)
+            ),
+            "requests must be spaced out by the emission interval"
+        );
+        assert!(gcra
+            .check_and_modify_at(&quota, now + Duration::from_millis(100), 1)
+            .is_ok());
+
+        // idle time doesn't build up a burst
+        let later = now + Duration::from_secs(10);
+        assert!(gcra.check_and_modify_at(&quota, later, 1).is_ok());
+        assert!(gcra.check_and_modify_at(&quota, later, 1).is_err());
+    }
+
+    #[test]
+    fn quota_unlimited() {
+        let quota = Quota::unlimited();
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        for cost in [0, 1, u32::MAX] {
+            assert!(gcra.check_and_modify_at(&quota, now, cost).is_ok());
+        }
+        gcra.revert_at(&quota, now, 1);
+        assert_eq!(u32::MAX, gcra.remaining_resources(&quota, now));
+        assert_eq!("unlimited", quota.to_string());
+        assert!(quota > Quota::new(u32::MAX, Duration::from_nanos(1)));
+    }
+
+    #[test]
+    fn quota_zero() {
+        let quota = Quota::zero();
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        for cost in [0, 1, u32::MAX] {
+            assert!(matches!(
+                gcra.check_and_modify_at(&quota, now, cost),
+                Err(Error::DeniedIndefinitely(denied)) if denied == cost
+            ));
+        }
+        gcra.revert_at(&quota, now, 1);
+        assert_eq!(0, gcra.remaining_resources(&quota, now));
+        assert_eq!(None, gcra.tat);
+        assert_eq!("zero", quota.to_string());
+        assert!(quota < Quota::new(1, Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn gcra_check_within_deadline() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            now,
+            gcra.check_within(&quota, now, 2, now).unwrap(),
+            "passes right away"
+        );
+
+        let deadline = now + Duration::from_millis(999);
+        assert!(
+            matches!(gcra.check_within(&quota, now, 1, deadline), Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(1)),
+            "wouldn't pass before the deadline"
+        );
+        assert_eq!(Some(now + Duration::from_secs(2)), gcra.tat);
+
+        let deadline = now + Duration::from_secs(1);
+        assert_eq!(
+            deadline,
+            gcra.check_within(&quota, now, 1, deadline).unwrap(),
+            "passes by waiting until the deadline"
+        );
+        assert_eq!(
+            Some(now + Duration::from_secs(3)),
+            gcra.tat,
+            "resources are booked at the time the caller proceeds"
+        );
+    }
+
+    #[test]
+    fn error_with_jitter() {
+        let now = Instant::now();
+        let max_jitter = Duration::from_secs(1);
+
+        assert!(matches!(
+            Error::DeniedUntil(now).with_jitter_ratio(max_jitter, 0.5),
+            Error::DeniedUntil(next) if next == now + Duration::from_millis(500)
+        ));
+        assert!(matches!(
+            Error::DeniedUntil(now).with_jitter_ratio(max_jitter, 2.0),
+            Error::DeniedUntil(next) if next == now + max_jitter
+        ));
+        assert!(matches!(
+            Error::DeniedUntil(now).with_jitter_ratio(max_jitter, f64::NAN),
+            Error::DeniedUntil(next) if next == now
+        ));
+        assert!(matches!(
+            Error::DeniedUntil(now).with_jitter_ratio(Duration::MAX, 1.0),
+            Error::DeniedUntil(next) if next == now
+        ));
+        assert!(matches!(
+            Error::DeniedIndefinitely(3).with_jitter(max_jitter),
+            Error::DeniedIndefinitely(3)
+        ));
+
+        let jittered: Vec<_> = (0..16)
+            .map(|_| match Error::DeniedUntil(now).with_jitter(max_jitter) {
+                Error::DeniedUntil(next) => next,
+                err => panic!("unexpected {:?}", err),
+            })
+            .collect();
+        assert!(jittered
+            .iter()
+            .all(|next| *next >= now && *next <= now + max_jitter));
+        assert!(
+            jittered.iter().any(|next| *next != jittered[0]),
+            "retries should be spread out"
+        );
+    }
+
+    #[test]
+    fn gcra_early_throttling() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        // utilization stays below the threshold, the sample doesn't matter
+        for _ in 0..5 {
+            assert!(gcra
+                .check_and_modify_early_sampled_at(&quota, now, 1, 0.5, 0.0)
+                .is_ok());
+        }
+
+        // 60% utilized, denied with a probability of 20%
+        assert!(gcra
+            .check_and_modify_early_sampled_at(&quota, now, 1, 0.5, 0.2)
+            .is_ok());
+        assert!(
+            matches!(
+                gcra.check_and_modify_early_sampled_at(&quota, now, 1, 0.5, 0.3),
+                Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(2)
+            ),
+            "70% utilized, denied with a probability of 40%"
+        );
+        assert_eq!(
+            Some(now + Duration::from_secs(6)),
+            gcra.tat,
+            "early denials leave the state unchanged"
+        );
+
+        // regular denials still apply
+        let mut full = State {
+            tat: Some(now + quota.period),
+        };
+        assert!(matches!(
+            full.check_and_modify_early_at(&quota, now, 1, 0.5),
+            Err(Error::DeniedUntil(next)) if next == now + quota.emission_interval
+        ));
+    }
 
-    /// Incremental duration cost of a single resource check
-    pub emission_interval: Duration,
-}
+    #[test]
+    fn gcra_pressure() {
+        let quota = Quota::new(4, Duration::from_secs(4));
+        let now = Instant::now();
 
-impl Quota {
-    pub fn new(resource_limit: u32, period: Duration) -> Self {
-        let emission_interval = period / resource_limit;
+        assert_eq!(0.0, State::default().pressure(&quota, now));
+        let state = State {
+            tat: Some(now + Duration::from_secs(3)),
+        };
+        assert_eq!(0.75, state.pressure(&quota, now));
+        assert_eq!(0.25, state.pressure(&quota, now + Duration::from_secs(2)));
 
-        Self {
-            resource_limit,
-            period,
-            emission_interval,
-        }
+        assert_eq!(1.0, state.pressure(&Quota::zero(), now));
+        assert_eq!(0.0, state.pressure(&Quota::unlimited(), now));
     }
 
-    /// Given a `cost`, calculates the increment interval.
-    #[inline]
-    pub fn increment_interval(&self, cost: u32) -> Duration {
-        self.emission_interval * cost
+    #[test]
+    fn gcra_time_overflow() {
+        let quota = Quota::new(1, Duration::MAX);
+        let now = Instant::now();
+
+        let mut gcra = State::default();
+        assert!(matches!(
+            gcra.check_and_modify_at(&quota, now, 1),
+            Err(Error::TimeOverflow)
+        ));
+        assert_eq!(None, gcra.tat, "state should be unchanged");
+
+        let mut gcra = State { tat: Some(now) };
+        gcra.revert_at(&quota, now, 1);
+        assert_eq!(
+            Some(now),
+            gcra.tat,
+            "refunds are bounded by what is consumed"
+        );
     }
-}
 
-#[derive(Debug)]
-pub enum Error {
-    /// Cost of the increment exceeds the rate limit and will never succeed
-    DeniedIndefinitely(u32),
+    #[test]
+    fn gcra_increment_overflow() {
+        let quota = Quota::new(1, Duration::from_secs(u64::MAX / 2));
+        assert_eq!(None, quota.checked_increment_interval(4));
+        assert_eq!(Duration::MAX, quota.increment_interval(4));
 
-    /// Limited request until after the [Instant]
-    DeniedUntil(Instant),
-}
+        let mut gcra = State::default();
+        assert!(matches!(
+            gcra.check_and_modify_at(&quota, Instant::now(), 4),
+            Err(Error::DeniedIndefinitely(4))
+        ));
+    }
 
-impl Display for Error {
-    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::DeniedIndefinitely(cost) => {
-                write!(
-                    fmt,
-                    "cost of the increment {} exceeds the rate limit and will never succeed",
-                    cost
-                )
-            }
-            Error::DeniedUntil(next) => write!(fmt, "denied until {:?}", next),
-        }
+    #[test]
+    fn gcra_zero_cost_probe() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        assert!(gcra.check_and_modify_at(&quota, now, 0).is_ok());
+        assert_eq!(None, gcra.tat, "zero cost should not modify the state");
+
+        assert!(gcra.check_and_modify_at(&quota, now, 1).is_ok());
+        assert!(gcra.probe_at(&quota, now).is_ok());
+        assert!(gcra.check_and_modify_at(&quota, now, 1).is_ok());
+
+        let tat = gcra.tat;
+        assert!(matches!(
+            gcra.probe_at(&quota, now),
+            Err(Error::DeniedUntil(next)) if next == now + quota.emission_interval
+        ));
+        assert!(matches!(
+            gcra.check_and_modify_at(&quota, now, 0),
+            Err(Error::DeniedUntil(next)) if next == now + quota.emission_interval
+        ));
+        assert_eq!(tat, gcra.tat, "probes should not modify the state");
     }
-}
 
-/// Holds the minimum amount of state necessary to implement a GCRA leaky buckets.
-/// Refer to: [understanding GCRA](https://blog.ian.stapletoncordas.co/2018/12/understanding-generic-cell-rate-limiting.html)
-#[derive(Default, Debug)]
-pub struct State {
-    /// GCRA's Theoretical Arrival Time (**TAT**)
-    /// An unset value signals a new state
-    pub tat: Option<Instant>,
-}
+    #[test]
+    fn gcra_lifecycle() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let mut gcra = State::default();
+        let now = Instant::now();
 
-impl State {
-    /// Check if we are allowed to proceed. If so updated our internal state and return true.
-    ///
-    /// Simply passes the current Instant to [`check_and_modify_at()`]
-    #[inline]
-    pub fn check_and_modify(&mut self, rate_limit: &Quota, cost: u32) -> Result<(), Error> {
-        self.check_and_modify_at(rate_limit, Instant::now(), cost)
-    }
+        assert!(gcra.is_expired(&quota, now));
+        assert_eq!(None, gcra.expires_at(&quota));
 
-    /// Check if we are allowed to proceed at the given arrival time.
-    /// If so updated our internal state and return true.
-    /// Explanation of GCRA can be found [here](https://blog.ian.stapletoncordas.co/2018/12/understanding-generic-cell-rate-limiting.html)
-    ///
-    /// # Returns
-    /// If denied, will return an [Result::Err] where the value is the next allowed timestamp.
-    pub fn check_and_modify_at(
-        &mut self,
-        rate_limit: &Quota,
-        arrived_at: Instant,
-        cost: u32,
-    ) -> Result<(), Error> {
-        let increment_interval = rate_limit.increment_interval(cost);
-        if increment_interval > rate_limit.period {
-            return Err(Error::DeniedIndefinitely(cost));
-        }
+        assert!(gcra.check_and_modify_at(&quota, now, 1).is_ok());
+        let expires_at = now + Duration::from_secs(1);
+        assert_eq!(Some(expires_at), gcra.expires_at(&quota));
+        assert!(!gcra.is_expired(&quota, now));
+        assert!(gcra.is_expired(&quota, expires_at));
+        assert!(gcra.is_expired(&Quota::unlimited(), now));
 
-        let tat = match self.tat {
-            Some(tat) => tat,
-            None => {
-                // First ever request. Allow passage and update self.
-                self.tat = Some(arrived_at + increment_interval);
-                return Ok(());
-            }
+        gcra.reset();
+        assert_eq!(None, gcra.tat);
+    }
+
+    #[test]
+    fn gcra_merge() {
+        let now = Instant::now();
+        let busy = State {
+            tat: Some(now + Duration::from_secs(4)),
+        };
+        let idle = State {
+            tat: Some(now - Duration::from_secs(4)),
         };
 
-        // We had a previous request
-        if tat < arrived_at {
-            // prev request was really old
-            let new_tat = std::cmp::max(tat, arrived_at);
-            self.tat = Some(new_tat + increment_interval);
-        } else {
-            // prev request was recent and there's a possibility that we've reached the limit
-            let delay_variation_tolerance = rate_limit.period;
-            let new_tat = tat + increment_interval;
-
-            let next_allowed_at = new_tat - delay_variation_tolerance;
-            if next_allowed_at <= arrived_at {
-                self.tat = Some(new_tat);
-            } else {
-                // Denied, must wait until next_allowed_at
-                return Err(Error::DeniedUntil(next_allowed_at));
-            }
-        }
+        let mut merged = idle.clone();
+        merged.merge_max(&busy);
+        assert_eq!(busy.tat, merged.tat);
+        merged.merge_max(&State::default());
+        assert_eq!(busy.tat, merged.tat);
 
-        Ok(())
+        let mut merged = busy.clone();
+        merged.merge_weighted(&idle, now, 0.5);
+        assert_eq!(
+            Some(now + Duration::from_secs(2)),
+            merged.tat,
+            "idle time counts as now"
+        );
+
+        let mut merged = idle.clone();
+        merged.merge_weighted(&busy, now, 0.25);
+        assert_eq!(Some(now + Duration::from_secs(1)), merged.tat);
+
+        let mut merged = busy.clone();
+        merged.merge_weighted(&State::default(), now, 1.0);
+        assert_eq!(None, merged.tat);
     }
 
-    /// Reverts rate_limit by cost, and updated our internal state.
-    ///
-    /// Simply passes the current Instant to [`revert_at()`]
-    #[inline]
-    pub fn revert(&mut self, rate_limit: &Quota, cost: u32) -> Result<(), Error> {
-        let arrived_at = Instant::now();
-        self.revert_at(rate_limit, arrived_at, cost)
+    #[test]
+    fn gcra_snapshot_diff() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        let before = gcra.snapshot_at(now);
+        gcra.check_and_modify_at(&quota, now, 3).unwrap();
+        let after = gcra.snapshot_at(now);
+        assert_eq!(Duration::from_secs(3), before.diff(&after));
+        assert_eq!(
+            Duration::ZERO,
+            after.diff(&before),
+            "reverts don't go negative"
+        );
+
+        // the state went idle in between, only the second check counts
+        let later = now + Duration::from_secs(10);
+        gcra.check_and_modify_at(&quota, later, 2).unwrap();
+        assert_eq!(Duration::from_secs(2), after.diff(&gcra.snapshot_at(later)));
     }
 
-    /// Reverts rate_limit by cost, and updated our internal state.
-    ///
-    /// This is a hack that substracts the incremental cost from the TAT.
-    pub fn revert_at(
-        &mut self,
-        rate_limit: &Quota,
-        arrived_at: Instant,
-        cost: u32,
-    ) -> Result<(), Error> {
-        let increment_interval = rate_limit.increment_interval(cost);
+    #[test]
+    fn gcra_consume_all() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        gcra.consume_all_at(&quota, now);
+        assert_eq!(0, gcra.remaining_resources(&quota, now));
+        assert!(matches!(
+            gcra.check_and_modify_at(&quota, now, 1),
+            Err(Error::DeniedUntil(next)) if next == now + quota.emission_interval
+        ));
+
+        // doesn't shorten a longer denial
+        let tat = gcra.tat;
+        gcra.consume_all_at(&quota, now - Duration::from_secs(1));
+        assert_eq!(tat, gcra.tat);
+    }
 
-        let compute_revert_tat = |new_tat: Instant| new_tat - increment_interval;
+    #[test]
+    fn gcra_with_fill() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let now = Instant::now();
 
-        let tat = match self.tat {
-            Some(tat) => tat,
-            None => {
-                // First ever request. Nothing to do.
-                return Ok(());
-            }
-        };
+        assert_eq!(None, State::with_fill(&quota, now, 1.0).tat);
+        assert_eq!(
+            0,
+            State::with_fill(&quota, now, 0.0).remaining_resources(&quota, now)
+        );
 
-        // We had a previous request
-        if tat < arrived_at {
-            // Reset state: prev request was really old
-            self.tat = None;
-        } else {
-            // prev request was recent
-            self.tat = Some(compute_revert_tat(tat));
-        }
-        Ok(())
+        let mut gcra = State::with_fill(&quota, now, 0.3);
+        assert_eq!(3, gcra.remaining_resources(&quota, now));
+        assert!(gcra.check_and_modify_at(&quota, now, 3).is_ok());
+        assert!(gcra.check_and_modify_at(&quota, now, 1).is_err());
+
+        assert_eq!(
+            0,
+            State::with_fill(&quota, now, f64::NAN).remaining_resources(&quota, now),
+            "NaN counts as empty"
+        );
     }
 
-    pub fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
-        if rate_limit.period.is_zero() {
-            return 0;
-        }
+    #[test]
+    fn gcra_estimate_wait() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
 
-        let time_to_tat = match self.tat.and_then(|tat| tat.checked_duration_since(now)) {
-            Some(duration_until) => duration_until.as_secs_f32(),
-            None => return rate_limit.resource_limit,
-        };
+        assert_eq!(Duration::ZERO, gcra.estimate_wait(&quota, now, 10).unwrap());
+        gcra.check_and_modify_at(&quota, now, 8).unwrap();
+        assert_eq!(Duration::ZERO, gcra.estimate_wait(&quota, now, 2).unwrap());
+        assert_eq!(
+            Duration::from_secs(3),
+            gcra.estimate_wait(&quota, now, 5).unwrap()
+        );
+        assert_eq!(
+            Some(now + Duration::from_secs(8)),
+            gcra.tat,
+            "estimating doesn't modify the state"
+        );
+        assert!(matches!(
+            gcra.estimate_wait(&quota, now, 11),
+            Err(Error::DeniedIndefinitely(11))
+        ));
+    }
 
-        // Logically this makes more sense as:
-        //   consumed_resources = time_to_tat * (resource_limit/period)
-        // but we run it this way because of Duration's arithmetic functions
-        let consumed_resources =
-            (time_to_tat * rate_limit.resource_limit as f32) / rate_limit.period.as_secs_f32();
-        rate_limit.resource_limit - consumed_resources.ceil() as u32
+    #[test]
+    fn gcra_time_until_n_available() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+        gcra.consume_all_at(&quota, now);
+
+        assert_eq!(
+            Duration::ZERO,
+            gcra.time_until_n_available(&quota, now, 0).unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(4),
+            gcra.time_until_n_available(&quota, now, 4).unwrap()
+        );
+        assert!(gcra.time_until_n_available(&quota, now, 11).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::time::Duration;
-    use super::*;
+    #[test]
+    fn gcra_remaining_f64() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        assert_eq!(10, gcra.remaining(&quota));
+        gcra.check_and_modify_at(&quota, now, 10).unwrap();
+        let later = now + Duration::from_millis(2500);
+        assert_eq!(2, gcra.remaining_resources(&quota, later));
+        assert!((gcra.remaining_f64_at(&quota, later) - 2.5).abs() < 1e-9);
+        assert_eq!(0.0, gcra.remaining_f64_at(&quota, now));
+    }
 
     #[test]
-    fn rate_limit_emission_interval() {
-        let rate_limit = Quota::new(10, Duration::from_secs(20));
-        assert_eq!(Duration::from_secs(2), rate_limit.emission_interval)
+    fn gcra_rescale() {
+        let old = Quota::new(10, Duration::from_secs(10));
+        let new = Quota::new(100, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        gcra.check_and_modify_at(&old, now, 4).unwrap();
+        gcra.rescale(&old, &new, now);
+        assert_eq!(96, gcra.remaining_resources(&new, now));
+
+        gcra.check_and_modify_at(&new, now, 96).unwrap();
+        gcra.rescale(&new, &old, now);
+        assert_eq!(
+            Some(now + old.period),
+            gcra.tat,
+            "never denied for longer than a full burst"
+        );
+
+        gcra.rescale(&old, &Quota::unlimited(), now);
+        assert_eq!(None, gcra.tat);
+
+        // consumption too long to represent at the new rate is clamped to its burst
+        let huge = Quota::new(4, Duration::from_secs(1 << 62)).with_burst(1);
+        let fast = Quota::new(1_000_000, Duration::from_secs(1));
+        gcra.check_and_modify_at(&fast, now, 1_000_000).unwrap();
+        gcra.rescale(&fast, &huge, now);
+        assert_eq!(Some(now + huge.delay_variation_tolerance()), gcra.tat);
+    }
+
+    #[test]
+    fn gcra_check_decision() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut gcra = State::default();
+        let now = Instant::now();
+
+        assert!(matches!(
+            gcra.check_decision_at(&quota, now, 8),
+            Decision::Allowed { remaining: 2, reset_after } if reset_after == Duration::from_secs(8)
+        ));
+        assert!(matches!(
+            gcra.check_decision_at(&quota, now, 4),
+            Decision::Throttled { retry_after } if retry_after == Duration::from_secs(2)
+        ));
+        assert!(matches!(
+            gcra.check_decision_at(&quota, now, 11),
+            Decision::Blocked {
+                reason: Error::DeniedIndefinitely(11)
+            }
+        ));
     }
 
     #[test]
@@ -303,10 +1768,7 @@ mod tests {
 
         let req_ts = Instant::now();
         // Revert before any calls
-        assert!(
-            gcra.revert_at(&rate_limit, req_ts, 1).is_ok(),
-            "revert should have released resources"
-        );
+        gcra.revert_at(&rate_limit, req_ts, 1);
         assert_eq!(None, gcra.tat, "state should not have changed at all",);
     }
 
@@ -329,10 +1791,7 @@ mod tests {
         );
 
         // Revert
-        assert!(
-            gcra.revert_at(&rate_limit, req_ts, 1).is_ok(),
-            "revert should have released resources"
-        );
+        gcra.revert_at(&rate_limit, req_ts, 1);
         assert_eq!(
             Some(req_ts + rate_limit.period - rate_limit.increment_interval(1)),
             gcra.tat,
@@ -365,10 +1824,7 @@ mod tests {
 
         // Revert using current time
         let req_ts = Instant::now();
-        assert!(
-            gcra.revert_at(&rate_limit, req_ts, 1).is_ok(),
-            "revert should have released resources"
-        );
+        gcra.revert_at(&rate_limit, req_ts, 1);
         assert_eq!(
             None, gcra.tat,
             "state should have reset since it was so old",
@@ -385,6 +1841,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn gcra_revert_bounded() {
+        let rate_limit = Quota::new(5, Duration::from_secs(1));
+        let mut gcra = State::default();
+        let req_ts = Instant::now();
+
+        assert!(gcra.check_and_modify_at(&rate_limit, req_ts, 1).is_ok());
+        gcra.revert_at(&rate_limit, req_ts, 5);
+        assert_eq!(
+            Some(req_ts),
+            gcra.tat,
+            "refund should be bounded by what was consumed"
+        );
+        assert_eq!(5, gcra.remaining_resources(&rate_limit, req_ts));
+    }
+
+    #[test]
+    fn gcra_cancel_booking() {
+        let rate_limit = Quota::new(5, Duration::from_secs(1));
+        let mut gcra = State::default();
+        let req_ts = Instant::now();
+
+        let first = gcra.book_at(&rate_limit, req_ts, 5).unwrap();
+        assert_eq!(req_ts, first.start());
+        assert_eq!(req_ts + rate_limit.period, first.end());
+
+        // the first booking fully leaked, and its capacity was consumed again
+        let later = req_ts + rate_limit.period;
+        let second = gcra.book_at(&rate_limit, later, 5).unwrap();
+        gcra.cancel_at(first, later);
+        assert_eq!(
+            Some(later + rate_limit.period),
+            gcra.tat,
+            "leaked bookings refund nothing"
+        );
+
+        // half of the second booking is still held
+        let halfway = later + rate_limit.period / 2;
+        gcra.cancel_at(second, halfway);
+        assert_eq!(Some(halfway), gcra.tat);
+        assert_eq!(5, gcra.remaining_resources(&rate_limit, halfway));
+
+        let probe = gcra.book_at(&rate_limit, halfway, 0).unwrap();
+        assert_eq!(probe.start(), probe.end(), "probes book nothing");
+    }
+
+    #[test]
+    fn gcra_cancel_booking_once() {
+        let rate_limit = Quota::new(5, Duration::from_secs(5));
+        let mut gcra = State::default();
+        let req_ts = Instant::now();
+
+        let _first = gcra.book_at(&rate_limit, req_ts, 2).unwrap();
+        let second = gcra.book_at(&rate_limit, req_ts, 2).unwrap();
+        assert_eq!(1, gcra.remaining_resources(&rate_limit, req_ts));
+
+        // the booking is moved into cancel_at, a second cancel doesn't compile
+        gcra.cancel_at(second, req_ts);
+        assert_eq!(3, gcra.remaining_resources(&rate_limit, req_ts));
+    }
+
     #[test]
     fn gcra_leaky() {
         // const INCREMENT_INTERVAL: u64 = 500;
@@ -506,4 +2023,47 @@ mod tests {
             "request #2 should fail"
         );
     }
+
+    #[test]
+    fn ticks_round_up() {
+        let epoch = Instant::now();
+        let resolution = Duration::from_millis(1);
+        let state = State {
+            tat: Some(epoch + Duration::from_micros(2500)),
+        };
+
+        let ticks = state.to_ticks(epoch, resolution);
+        assert_eq!(3, ticks, "TAT should be rounded up to the next tick");
+
+        let decoded = State::from_ticks(ticks, epoch, resolution).unwrap();
+        assert_eq!(Some(epoch + Duration::from_millis(3)), decoded.tat);
+        assert!(
+            decoded.tat >= state.tat,
+            "decoded state must never under-enforce"
+        );
+    }
+
+    #[test]
+    fn ticks_past_nanos_range() {
+        let epoch = Instant::now();
+        let resolution = Duration::from_millis(1);
+
+        // more nanoseconds than a u64 holds
+        let decoded = State::from_ticks(1 << 45, epoch, resolution).unwrap();
+        assert_eq!(Some(epoch + Duration::from_millis(1 << 45)), decoded.tat);
+        assert_eq!(1 << 45, decoded.to_ticks(epoch, resolution));
+
+        let resolution = Duration::from_secs(u64::MAX);
+        assert!(State::from_ticks(u64::MAX, epoch, resolution).is_none());
+    }
+
+    #[test]
+    fn ticks_expired_and_unset() {
+        let epoch = Instant::now();
+        let resolution = Duration::from_millis(1);
+
+        assert_eq!(0, State::default().to_ticks(epoch, resolution));
+        assert_eq!(0, State { tat: Some(epoch) }.to_ticks(epoch, resolution));
+        assert_eq!(None, State::from_ticks(0, epoch, resolution).unwrap().tat);
+    }
 }
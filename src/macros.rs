@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+/// Defines a [`Quota`](crate::Quota) from a literal, evaluated at compile time.
+///
+/// The period is a string of one or more `<integer><unit>` pairs, where unit is one of
+/// `ns`, `us`, `ms`, `s`, `m`, `h` or `d`. Zero limits, zero bursts and malformed periods
+/// fail the build instead of panicking at runtime.
+///
+/// ```
+/// use gcra::quota;
+///
+/// const LOGIN: gcra::Quota = quota!(5 / "1m");
+/// let api = quota!(100 / "10s", burst = 20);
+///
+/// assert_eq!(5, LOGIN.resource_limit);
+/// assert_eq!(20, api.burst);
+/// ```
+///
+/// ```compile_fail
+/// let quota = gcra::quota!(0 / "1s");
+/// ```
+#[macro_export]
+macro_rules! quota {
+    ($limit:literal / $period:literal) => {{
+        const QUOTA: $crate::Quota = $crate::Quota::new($limit, $crate::parse_period($period));
+        QUOTA
+    }};
+    ($limit:literal / $period:literal, burst = $burst:literal) => {{
+        const QUOTA: $crate::Quota =
+            $crate::Quota::new($limit, $crate::parse_period($period)).with_burst($burst);
+        QUOTA
+    }};
+}
+
+/// Parses periods like `10s` or `1m30s` for [`quota!`].
+///
+/// # Panics
+/// If the period is malformed or zero.
+pub const fn parse_period(period: &str) -> Duration {
+    match try_parse_period(period) {
+        Some(period) if period.is_zero() => panic!("period must be greater than zero"),
+        Some(period) => period,
+        None => panic!("malformed period, units must be one of ns, us, ms, s, m, h or d"),
+    }
+}
+
+/// Parses periods like [`parse_period()`], zero ones included, where a bare `0` is a
+/// zero period as well.
+///
+/// # Returns
+/// `None` if the period is malformed or overflows.
+pub const fn try_parse_period(period: &str) -> Option<Duration> {
+    let bytes = period.as_bytes();
+    if bytes.is_empty() {
+        return None;
+    }
+    if bytes.len() == 1 && bytes[0] == b'0' {
+        return Some(Duration::ZERO);
+    }
+
+    let mut total: u128 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut value: u64 = 0;
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            value = match value.checked_mul(10) {
+                Some(value) => match value.checked_add((bytes[i] - b'0') as u64) {
+                    Some(value) => value,
+                    None => return None,
+                },
+                None => return None,
+            };
+            i += 1;
+        }
+        if i == start {
+            // must start with an integer
+            return None;
+        }
+
+        // nanoseconds per unit and the length of the unit suffix
+        let (unit, len): (u128, usize) = match (byte_at(bytes, i), byte_at(bytes, i + 1)) {
+            (b'n', b's') => (1, 2),
+            (b'u', b's') => (1_000, 2),
+            (b'm', b's') => (1_000_000, 2),
+            (b's', _) => (1_000_000_000, 1),
+            (b'm', _) => (60 * 1_000_000_000, 1),
+            (b'h', _) => (60 * 60 * 1_000_000_000, 1),
+            (b'd', _) => (24 * 60 * 60 * 1_000_000_000, 1),
+            _ => return None,
+        };
+        i += len;
+
+        total += value as u128 * unit;
+    }
+
+    if total / 1_000_000_000 > u64::MAX as u128 {
+        return None;
+    }
+    Some(Duration::new(
+        (total / 1_000_000_000) as u64,
+        (total % 1_000_000_000) as u32,
+    ))
+}
+
+/// `bytes[index]`, or `0` when out of bounds.
+const fn byte_at(bytes: &[u8], index: usize) -> u8 {
+    if index < bytes.len() {
+        bytes[index]
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quota;
+
+    #[test]
+    fn parse_periods() {
+        assert_eq!(Duration::from_nanos(10), parse_period("10ns"));
+        assert_eq!(Duration::from_micros(10), parse_period("10us"));
+        assert_eq!(Duration::from_millis(250), parse_period("250ms"));
+        assert_eq!(Duration::from_secs(10), parse_period("10s"));
+        assert_eq!(Duration::from_secs(90), parse_period("1m30s"));
+        assert_eq!(Duration::from_secs(2 * 3600), parse_period("2h"));
+        assert_eq!(Duration::from_secs(86400), parse_period("1d"));
+    }
+
+    #[test]
+    #[should_panic(expected = "malformed period")]
+    fn parse_period_unknown_unit() {
+        parse_period("10x");
+    }
+
+    #[test]
+    fn try_parse_periods() {
+        assert_eq!(Some(Duration::from_secs(90)), try_parse_period("1m30s"));
+        assert_eq!(Some(Duration::ZERO), try_parse_period("0"));
+        assert_eq!(Some(Duration::ZERO), try_parse_period("0ms"));
+        assert_eq!(None, try_parse_period(""));
+        assert_eq!(None, try_parse_period("10"));
+        assert_eq!(None, try_parse_period("s"));
+        assert_eq!(None, try_parse_period("10x"));
+        assert_eq!(None, try_parse_period("99999999999999999999s"));
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn parse_period_zero() {
+        parse_period("0s");
+    }
+
+    #[test]
+    fn quota_macro() {
+        assert_eq!(
+            Quota::new(100, Duration::from_secs(10)),
+            quota!(100 / "10s")
+        );
+        assert_eq!(
+            Quota::new(100, Duration::from_secs(10)).with_burst(20),
+            quota!(100 / "10s", burst = 20)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, RateLimitAlgorithm, State};
+
+/// What [MonotonicState] does with an arrival older than a previous one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Regression {
+    /// Treat it as arriving at the same time as the latest arrival.
+    #[default]
+    Clamp,
+
+    /// Deny it with [Error::TimeRegressed].
+    Reject,
+}
+
+/// A [State] guarding against arrival times going backwards, e.g. when callers cache
+/// timestamps, which would otherwise skew the TAT.
+#[derive(Clone, Debug, Default)]
+pub struct MonotonicState {
+    pub state: State,
+    regression: Regression,
+    last_arrival: Option<Instant>,
+}
+
+impl MonotonicState {
+    pub fn new(regression: Regression) -> Self {
+        Self {
+            state: State::default(),
+            regression,
+            last_arrival: None,
+        }
+    }
+
+    /// Latest arrival time seen.
+    pub fn last_arrival(&self) -> Option<Instant> {
+        self.last_arrival
+    }
+
+    /// Applies the regression policy, and records the arrival.
+    fn arrive(&mut self, arrived_at: Instant) -> Result<Instant, Error> {
+        match self.last_arrival {
+            Some(last) if arrived_at < last => match self.regression {
+                Regression::Clamp => Ok(last),
+                Regression::Reject => Err(Error::TimeRegressed(last)),
+            },
+            _ => {
+                self.last_arrival = Some(arrived_at);
+                Ok(arrived_at)
+            }
+        }
+    }
+}
+
+impl RateLimitAlgorithm for MonotonicState {
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        let arrived_at = self.arrive(arrived_at)?;
+        self.state.check_and_modify_at(rate_limit, arrived_at, cost)
+    }
+
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        // reverting never moves the TAT forward, so stale arrivals are clamped whatever
+        // the regression policy
+        let arrived_at = match self.last_arrival {
+            Some(last) if arrived_at < last => last,
+            _ => {
+                self.last_arrival = Some(arrived_at);
+                arrived_at
+            }
+        };
+        self.state.revert_at(rate_limit, arrived_at, cost)
+    }
+
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        let now = self.last_arrival.map_or(now, |last| last.max(now));
+        self.state.remaining_resources(rate_limit, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn clamps_regressions() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let mut state = MonotonicState::new(Regression::Clamp);
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+
+        assert!(state.check_and_modify_at(&quota, later, 1).is_ok());
+        assert!(state.check_and_modify_at(&quota, now, 1).is_ok());
+        assert_eq!(Some(later), state.last_arrival());
+        assert_eq!(
+            Some(later + Duration::from_secs(2)),
+            state.state.tat,
+            "the stale arrival is booked at the latest arrival"
+        );
+    }
+
+    #[test]
+    fn rejects_regressions() {
+        let quota = Quota::new(2, Duration::from_secs(2));
+        let mut state = MonotonicState::new(Regression::Reject);
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+
+        assert!(state.check_and_modify_at(&quota, later, 1).is_ok());
+        assert!(matches!(
+            state.check_and_modify_at(&quota, now, 1),
+            Err(Error::TimeRegressed(last)) if last == later
+        ));
+        assert_eq!(Some(later + Duration::from_secs(1)), state.state.tat);
+
+        state.revert_at(&quota, now, 1);
+        assert_eq!(
+            Some(later),
+            state.state.tat,
+            "stale reverts are clamped rather than rejected"
+        );
+    }
+}
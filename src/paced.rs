@@ -0,0 +1,114 @@
+use std::sync::mpsc::{SendError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+/// Wraps an [mpsc](std::sync::mpsc) [Sender], delaying sends so they conform to a
+/// quota, which smooths producer bursts into downstream systems.
+///
+/// Clones share the quota, so cloning a sender doesn't multiply its rate.
+#[derive(Debug)]
+pub struct PacedSender<T> {
+    sender: Sender<T>,
+    quota: Quota,
+    state: Arc<Mutex<State>>,
+}
+
+impl<T> Clone for PacedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            quota: self.quota,
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<T> PacedSender<T> {
+    /// # Panics
+    /// If the quota is zero, as nothing could ever be sent.
+    pub fn new(sender: Sender<T>, quota: Quota) -> Self {
+        assert!(!quota.is_zero(), "quota must allow sending");
+
+        Self {
+            sender,
+            quota,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Blocks the current thread until the quota allows another message, then sends it.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let until = self.reserve_at(Instant::now());
+        std::thread::sleep(until.saturating_duration_since(Instant::now()));
+        self.sender.send(value)
+    }
+
+    /// Books the next message as of `now`, returning when it may be sent.
+    fn reserve_at(&self, now: Instant) -> Instant {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let mut at = now;
+        loop {
+            match state.check_and_modify_at(&self.quota, at, 1) {
+                Ok(()) => return at,
+                Err(Error::DeniedUntil(next_allowed_at)) => at = next_allowed_at,
+                // a single message fits any non-zero burst, and past the
+                // representable times, sending right away is all we can do
+                Err(_) => return at,
+            }
+        }
+    }
+
+    /// The wrapped sender.
+    pub fn get_ref(&self) -> &Sender<T> {
+        &self.sender
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn paced_sender_spaces_out_sends() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = PacedSender::new(sender, Quota::new(1, Duration::from_millis(20)));
+
+        let start = Instant::now();
+        for i in 0..3 {
+            sender.send(i).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(vec![0, 1, 2], receiver.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn paced_sender_books_slots() {
+        let (sender, _receiver) = mpsc::channel::<()>();
+        let quota = Quota::new(10, Duration::from_secs(1)).with_burst(2);
+        let sender = PacedSender::new(sender, quota);
+        let now = Instant::now();
+
+        assert_eq!(now, sender.reserve_at(now));
+        assert_eq!(now, sender.reserve_at(now));
+        assert_eq!(now + Duration::from_millis(100), sender.reserve_at(now));
+        assert_eq!(now + Duration::from_millis(200), sender.reserve_at(now));
+    }
+
+    #[test]
+    fn paced_sender_clones_share_quota() {
+        let (sender, _receiver) = mpsc::channel::<()>();
+        let quota = Quota::new(10, Duration::from_secs(1)).with_burst(1);
+        let sender = PacedSender::new(sender, quota);
+        let clone = sender.clone();
+        let now = Instant::now();
+
+        assert_eq!(now, sender.reserve_at(now));
+        assert_eq!(now + Duration::from_millis(100), clone.reserve_at(now));
+        assert_eq!(now + Duration::from_millis(200), sender.reserve_at(now));
+    }
+}
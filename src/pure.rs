@@ -0,0 +1,236 @@
+use crate::Quota;
+
+/// Denial of a [check].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Denied {
+    /// Cost of the increment exceeds the rate limit and will never succeed
+    Indefinitely(u32),
+
+    /// Limited request until after the timestamp
+    Until(u64),
+}
+
+/// GCRA on plain integers: checks `cost` resources arriving at `now`, against the TAT
+/// left by the previous check, if any.
+///
+/// Timestamps are nanoseconds since an epoch of the caller's choosing. Nothing is mutated,
+/// which makes it usable for FFI, STM-like retries or in const contexts, and it admits
+/// exactly what [`State::check_and_modify_at()`](crate::State::check_and_modify_at) does.
+///
+/// # Returns
+/// The new TAT to store if allowed. TATs which would overflow `u64` are denied indefinitely.
+pub const fn check(
+    quota: &Quota,
+    prev_tat: Option<u64>,
+    now: u64,
+    cost: u32,
+) -> Result<u64, Denied> {
+    if quota.is_zero() {
+        return Err(Denied::Indefinitely(cost));
+    }
+    if quota.is_unlimited() {
+        return Ok(match prev_tat {
+            Some(tat) => tat,
+            None => now,
+        });
+    }
+
+    if cost == 0 {
+        // a free probe, like State's
+        return match check(quota, prev_tat, now, 1) {
+            Ok(_) => Ok(match prev_tat {
+                Some(tat) => tat,
+                None => now,
+            }),
+            Err(Denied::Indefinitely(_)) => Err(Denied::Indefinitely(cost)),
+            Err(denied) => Err(denied),
+        };
+    }
+
+    let increment_interval = quota.emission_interval.as_nanos() * cost as u128;
+    let delay_variation_tolerance = quota.delay_variation_tolerance().as_nanos();
+    if increment_interval > delay_variation_tolerance {
+        return Err(Denied::Indefinitely(cost));
+    }
+
+    let tat = match prev_tat {
+        // prev request was recent
+        Some(tat) if tat >= now => tat as u128,
+        // first ever request, or prev request was really old
+        _ => now as u128,
+    };
+
+    let new_tat = tat + increment_interval;
+    if new_tat > u64::MAX as u128 {
+        return Err(Denied::Indefinitely(cost));
+    }
+
+    let next_allowed_at = new_tat.saturating_sub(delay_variation_tolerance);
+    if next_allowed_at <= now as u128 {
+        Ok(new_tat as u64)
+    } else {
+        // Denied, must wait until next_allowed_at
+        Err(Denied::Until(next_allowed_at as u64))
+    }
+}
+
+/// Classifies many TATs against one quota at once: sets `throttled[i]` when a check of
+/// `cost` at `now` against `tats[i]` would be denied by [check], for analytics and GC
+/// jobs scanning lots of states.
+///
+/// TATs use [check]'s timestamps, where `0` stands for a new state. The comparison
+/// is the same for every state, so the loop vectorizes.
+///
+/// # Returns
+/// Amount of throttled states.
+///
+/// # Panics
+/// If `tats` and `throttled` differ in length.
+pub fn classify(quota: &Quota, now: u64, cost: u32, tats: &[u64], throttled: &mut [bool]) -> usize {
+    assert_eq!(
+        tats.len(),
+        throttled.len(),
+        "tats and throttled must have the same length"
+    );
+
+    if quota.is_zero() {
+        throttled.fill(true);
+        return tats.len();
+    }
+    if quota.is_unlimited() {
+        throttled.fill(false);
+        return 0;
+    }
+
+    // probes check a single resource
+    let increment_interval = quota.emission_interval.as_nanos() * cost.max(1) as u128;
+    let delay_variation_tolerance = quota.delay_variation_tolerance().as_nanos();
+    // every check overflows once the increment alone doesn't fit in a TAT
+    if increment_interval > delay_variation_tolerance || increment_interval > u64::MAX as u128 {
+        throttled.fill(true);
+        return tats.len();
+    }
+
+    // a check passes when the TAT is at most this, stale TATs always being below `now`,
+    // and is denied as well when the new TAT would overflow
+    let max_tat = (now as u128 + delay_variation_tolerance - increment_interval)
+        .min(u64::MAX as u128 - increment_interval) as u64;
+
+    let mut count = 0;
+    for (tat, throttled) in tats.iter().zip(throttled.iter_mut()) {
+        *throttled = *tat > max_tat;
+        count += *throttled as usize;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{Error, State};
+
+    #[test]
+    fn matches_state() {
+        let quota = Quota::new(5, Duration::from_secs(1)).with_burst(3);
+        let epoch = Instant::now();
+        let mut state = State::default();
+        let mut tat = None;
+
+        for (millis, cost) in [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (0, 0),
+            (0, 1),
+            (100, 1),
+            (300, 2),
+            (300, 4),
+            (5000, 3),
+        ] {
+            let now = epoch + Duration::from_millis(millis);
+            let nanos = now.duration_since(epoch).as_nanos() as u64;
+
+            match (
+                state.check_and_modify_at(&quota, now, cost),
+                check(&quota, tat, nanos, cost),
+            ) {
+                // probes leave the state as it was
+                (Ok(()), Ok(_)) if cost == 0 => {}
+                (Ok(()), Ok(new_tat)) => {
+                    assert_eq!(
+                        state
+                            .tat
+                            .map(|tat| tat.duration_since(epoch).as_nanos() as u64),
+                        Some(new_tat)
+                    );
+                    tat = Some(new_tat);
+                }
+                (Err(Error::DeniedUntil(next)), Err(Denied::Until(until))) => {
+                    assert_eq!(next.duration_since(epoch).as_nanos() as u64, until);
+                }
+                (Err(Error::DeniedIndefinitely(a)), Err(Denied::Indefinitely(b))) => {
+                    assert_eq!(a, b)
+                }
+                (a, b) => panic!("{:?} and {:?} disagree at {}ms", a, b, millis),
+            }
+        }
+    }
+
+    #[test]
+    fn classify_matches_check() {
+        for quota in [
+            Quota::new(5, Duration::from_secs(1)).with_burst(3),
+            Quota::new(1, Duration::from_secs(u64::MAX / 1000)),
+        ] {
+            classify_matches_check_with(&quota);
+        }
+    }
+
+    fn classify_matches_check_with(quota: &Quota) {
+        let now = 10_000_000_000;
+        let tats: Vec<u64> = vec![
+            0,
+            now - 1,
+            now,
+            now + 400_000_000,
+            now + 401_000_000,
+            now + 600_000_000,
+            u64::MAX,
+        ];
+
+        for cost in [0, 1, 2, 4] {
+            let mut throttled = vec![false; tats.len()];
+            let count = classify(quota, now, cost, &tats, &mut throttled);
+
+            for (tat, throttled) in tats.iter().zip(&throttled) {
+                let prev_tat = if *tat == 0 { None } else { Some(*tat) };
+                assert_eq!(
+                    check(quota, prev_tat, now, cost).is_err(),
+                    *throttled,
+                    "{} tat {} cost {}",
+                    quota,
+                    tat,
+                    cost
+                );
+            }
+            assert_eq!(throttled.iter().filter(|t| **t).count(), count);
+        }
+    }
+
+    #[test]
+    fn const_check() {
+        const QUOTA: Quota = Quota::new(1, Duration::from_secs(1));
+        const TAT: Result<u64, Denied> = check(&QUOTA, None, 10, 1);
+        assert_eq!(Ok(1_000_000_010), TAT);
+        assert_eq!(
+            Err(Denied::Until(1_000_000_010)),
+            check(&QUOTA, Some(1_000_000_010), 10, 1)
+        );
+        assert_eq!(
+            Err(Denied::Indefinitely(1)),
+            check(&QUOTA, Some(u64::MAX), 10, 1)
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use crate::{Error, Quota, State};
+
+/// Where a client stands with its quota, as reported in rate limit headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// The burst a client may use at once
+    pub limit: u32,
+
+    /// Resources still available
+    pub remaining: u32,
+
+    /// Time until the whole burst is available again
+    pub reset: Duration,
+}
+
+impl RateLimitInfo {
+    /// The quota info of `state` at `now`.
+    pub fn new(state: &State, rate_limit: &Quota, now: Instant) -> Self {
+        let reset = state
+            .expires_at(rate_limit)
+            .map_or(Duration::ZERO, |expires_at| {
+                expires_at.saturating_duration_since(now)
+            });
+
+        Self {
+            limit: rate_limit.burst,
+            remaining: state.remaining_resources(rate_limit, now),
+            reset,
+        }
+    }
+
+    /// The `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers,
+    /// with durations in whole seconds rounded up.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("RateLimit-Limit", self.limit.to_string()),
+            ("RateLimit-Remaining", self.remaining.to_string()),
+            ("RateLimit-Reset", ceil_secs(self.reset).to_string()),
+        ]
+    }
+}
+
+/// A framework agnostic `429 Too Many Requests` response for a denial, so every
+/// integration answers the same way.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use gcra::{Quota, RateLimitInfo, State, TooManyRequests};
+///
+/// let quota = Quota::new(1, Duration::from_secs(10));
+/// let mut state = State::default();
+/// let now = Instant::now();
+///
+/// state.check_and_modify_at(&quota, now, 1).unwrap();
+/// let err = state.check_and_modify_at(&quota, now, 1).unwrap_err();
+///
+/// let response = TooManyRequests::new(&err, RateLimitInfo::new(&state, &quota, now), now);
+/// assert_eq!(429, response.status());
+/// assert!(response.headers().contains(&("Retry-After", "10".to_string())));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TooManyRequests {
+    /// Time until a retry may pass, `None` if it never will or is unknown
+    pub retry_after: Option<Duration>,
+    /// True if the request exceeds the quota, so no retry will ever pass, as opposed to
+    /// a time error, which a later retry may get past
+    pub indefinitely: bool,
+    pub info: RateLimitInfo,
+}
+
+impl TooManyRequests {
+    /// The response for `denial`, as of `now`.
+    pub fn new(denial: &Error, info: RateLimitInfo, now: Instant) -> Self {
+        let retry_after = match denial {
+            Error::DeniedUntil(next) => Some(next.saturating_duration_since(now)),
+            Error::DeniedIndefinitely(_) | Error::TimeRegressed(_) | Error::TimeOverflow => None,
+        };
+
+        Self {
+            retry_after,
+            indefinitely: matches!(denial, Error::DeniedIndefinitely(_)),
+            info,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        429
+    }
+
+    /// The rate limit headers, preceded by `Retry-After` when a retry may pass.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::with_capacity(5);
+        headers.push(("Content-Type", "application/problem+json".to_string()));
+        if let Some(retry_after) = self.retry_after {
+            headers.push(("Retry-After", ceil_secs(retry_after).to_string()));
+        }
+        headers.extend(self.info.headers());
+        headers
+    }
+
+    /// An RFC 9457 problem details body.
+    pub fn body(&self) -> String {
+        let detail = match self.retry_after {
+            Some(retry_after) => format!(
+                "rate limit exceeded, retry in {} seconds",
+                ceil_secs(retry_after)
+            ),
+            None if self.indefinitely => {
+                "request exceeds the rate limit and will never succeed".to_string()
+            }
+            None => "rate limit could not be checked at this time, retry later".to_string(),
+        };
+
+        format!(
+            r#"{{"type":"about:blank","title":"Too Many Requests","status":429,"detail":"{}"}}"#,
+            detail
+        )
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_info() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        state.check_and_modify_at(&quota, now, 3).unwrap();
+        let info = RateLimitInfo::new(&state, &quota, now + Duration::from_millis(500));
+        assert_eq!(
+            vec![
+                ("RateLimit-Limit", "10".to_string()),
+                ("RateLimit-Remaining", "7".to_string()),
+                ("RateLimit-Reset", "3".to_string()),
+            ],
+            info.headers()
+        );
+    }
+
+    #[test]
+    fn too_many_requests_denied_indefinitely() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let now = Instant::now();
+        let info = RateLimitInfo::new(&State::default(), &quota, now);
+
+        let response = TooManyRequests::new(&Error::DeniedIndefinitely(11), info, now);
+        assert_eq!(None, response.retry_after);
+        assert!(response
+            .headers()
+            .iter()
+            .all(|(name, _)| *name != "Retry-After"));
+        assert!(response.body().contains("will never succeed"));
+    }
+
+    #[test]
+    fn too_many_requests_time_errors() {
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let now = Instant::now();
+        let info = RateLimitInfo::new(&State::default(), &quota, now);
+
+        for err in [Error::TimeRegressed(now), Error::TimeOverflow] {
+            let response = TooManyRequests::new(&err, info, now);
+            assert_eq!(None, response.retry_after);
+            assert!(!response.indefinitely);
+            assert!(response.body().contains("retry later"));
+        }
+    }
+}
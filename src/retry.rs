@@ -0,0 +1,152 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Quota, State};
+
+#[derive(Debug)]
+pub enum RetryError<E> {
+    /// The limiter will never allow an attempt
+    Limited(Error),
+
+    /// The last attempt failed, and it isn't retryable or no attempts are left
+    Operation(E),
+}
+
+impl<E: Display> Display for RetryError<E> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetryError::Limited(err) => Display::fmt(err, fmt),
+            RetryError::Operation(err) => Display::fmt(err, fmt),
+        }
+    }
+}
+
+/// Runs `op` for up to `max_attempts`, each attempt consuming one resource from `state`.
+///
+/// After a failure `retryable` accepts, waits for the longer of the next `backoff` delay
+/// and the time the limiter allows another attempt, sleeping the current thread. So the
+/// operation is never retried earlier than the limiter allows.
+///
+/// `op` always runs at least once, as its error is what a failed retry returns, so a
+/// `max_attempts` of zero behaves as one.
+pub fn retry_with_limit<T, E>(
+    rate_limit: &Quota,
+    state: &mut State,
+    max_attempts: u32,
+    backoff: impl IntoIterator<Item = Duration>,
+    retryable: impl Fn(&E) -> bool,
+    mut op: impl FnMut() -> Result<T, E>,
+) -> Result<T, RetryError<E>> {
+    let max_attempts = max_attempts.max(1);
+    let mut backoff = backoff.into_iter();
+    let mut attempts = 0;
+
+    loop {
+        // wait for the limiter
+        while let Err(err) = state.check_and_modify(rate_limit, 1) {
+            match err {
+                Error::DeniedUntil(next) => {
+                    std::thread::sleep(next.saturating_duration_since(Instant::now()))
+                }
+                err => return Err(RetryError::Limited(err)),
+            }
+        }
+
+        attempts += 1;
+        let err = match op() {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if attempts >= max_attempts || !retryable(&err) {
+            return Err(RetryError::Operation(err));
+        }
+
+        match backoff.next() {
+            Some(delay) => std::thread::sleep(delay),
+            None => return Err(RetryError::Operation(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_no_earlier_than_the_limiter_allows() {
+        let rate_limit = Quota::new(1, Duration::from_millis(20));
+        let mut state = State::default();
+        let start = Instant::now();
+        let mut calls = 0;
+
+        let result = retry_with_limit(
+            &rate_limit,
+            &mut state,
+            3,
+            std::iter::repeat(Duration::ZERO),
+            |_: &&str| true,
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err("flaky")
+                } else {
+                    Ok(calls)
+                }
+            },
+        );
+
+        assert_eq!(3, result.unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn gives_up() {
+        let rate_limit = Quota::new(10, Duration::from_millis(10));
+        let mut state = State::default();
+
+        let result: Result<(), _> = retry_with_limit(
+            &rate_limit,
+            &mut state,
+            5,
+            [Duration::from_millis(1)],
+            |err: &&str| *err == "flaky",
+            || Err("fatal"),
+        );
+        assert!(matches!(result, Err(RetryError::Operation("fatal"))));
+
+        let mut calls = 0;
+        let result: Result<(), _> = retry_with_limit(
+            &rate_limit,
+            &mut state,
+            5,
+            [Duration::from_millis(1)],
+            |_: &&str| true,
+            || {
+                calls += 1;
+                Err("flaky")
+            },
+        );
+        assert!(matches!(result, Err(RetryError::Operation("flaky"))));
+        assert_eq!(2, calls, "stops once the backoff policy gives up");
+
+        let result: Result<(), RetryError<&str>> =
+            retry_with_limit(&rate_limit, &mut state, 5, [], |_| true, || Ok(()));
+        assert!(result.is_ok());
+
+        let mut calls = 0;
+        let result: Result<(), _> = retry_with_limit(
+            &rate_limit,
+            &mut state,
+            0,
+            [Duration::from_millis(1)],
+            |_: &&str| true,
+            || {
+                calls += 1;
+                Err("flaky")
+            },
+        );
+        assert!(matches!(result, Err(RetryError::Operation("flaky"))));
+        assert_eq!(1, calls, "zero attempts behave as one");
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::{Decision, Error};
+
+/// Serializes errors as maps tagged by `error`, with the time left until an [Instant]
+/// as `retry_after_secs`, measured when serializing, and a human readable `message`.
+///
+/// [Instant]s are meaningless outside of the process, so they never show up as such.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            Error::DeniedIndefinitely(cost) => {
+                map.serialize_entry("error", "denied_indefinitely")?;
+                map.serialize_entry("cost", cost)?;
+                map.serialize_entry("message", &self.to_string())?;
+            }
+            Error::DeniedUntil(next) => {
+                map.serialize_entry("error", "denied_until")?;
+                let retry_after = next.saturating_duration_since(Instant::now()).as_secs_f64();
+                map.serialize_entry("retry_after_secs", &retry_after)?;
+                let message = format!("denied, retry after {:.3}s", retry_after);
+                map.serialize_entry("message", &message)?;
+            }
+            Error::TimeRegressed(_) => {
+                map.serialize_entry("error", "time_regressed")?;
+                map.serialize_entry("message", "arrived before the previous arrival")?;
+            }
+            Error::TimeOverflow => {
+                map.serialize_entry("error", "time_overflow")?;
+                map.serialize_entry("message", &self.to_string())?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Serializes decisions as maps tagged by `decision`, with durations in seconds.
+impl Serialize for Decision {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            Decision::Allowed {
+                remaining,
+                reset_after,
+            } => {
+                map.serialize_entry("decision", "allowed")?;
+                map.serialize_entry("remaining", remaining)?;
+                map.serialize_entry("reset_after_secs", &reset_after.as_secs_f64())?;
+            }
+            Decision::Throttled { retry_after } => {
+                map.serialize_entry("decision", "throttled")?;
+                map.serialize_entry("retry_after_secs", &retry_after.as_secs_f64())?;
+            }
+            Decision::Blocked { reason } => {
+                map.serialize_entry("decision", "blocked")?;
+                map.serialize_entry("reason", reason)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn serialize_decisions() {
+        let allowed = Decision::Allowed {
+            remaining: 2,
+            reset_after: Duration::from_millis(1500),
+        };
+        assert_eq!(
+            r#"{"decision":"allowed","remaining":2,"reset_after_secs":1.5}"#,
+            serde_json::to_string(&allowed).unwrap()
+        );
+
+        let blocked = Decision::Blocked {
+            reason: Error::DeniedIndefinitely(11),
+        };
+        assert_eq!(
+            r#"{"decision":"blocked","reason":{"error":"denied_indefinitely","cost":11,"message":"cost of the increment 11 exceeds the rate limit and will never succeed"}}"#,
+            serde_json::to_string(&blocked).unwrap()
+        );
+    }
+
+    #[test]
+    fn serialize_denied_until() {
+        // already passed when serializing, so the retry is due right away
+        let err = Error::DeniedUntil(Instant::now());
+        assert_eq!(
+            r#"{"error":"denied_until","retry_after_secs":0.0,"message":"denied, retry after 0.000s"}"#,
+            serde_json::to_string(&err).unwrap()
+        );
+
+        let err = Error::DeniedUntil(Instant::now() + Duration::from_secs(60));
+        let json: serde_json::Value = serde_json::to_value(&err).unwrap();
+
+        assert_eq!("denied_until", json["error"]);
+        let retry_after = json["retry_after_secs"].as_f64().unwrap();
+        assert!(retry_after > 59.0 && retry_after <= 60.0);
+        assert_eq!(
+            format!("denied, retry after {:.3}s", retry_after),
+            json["message"]
+        );
+    }
+}
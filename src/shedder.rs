@@ -0,0 +1,117 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+/// Sheds low priority work first as the [pressure](State::pressure) on a [State] rises.
+///
+/// Each priority is admitted up to its own pressure threshold, so with thresholds
+/// `[0.5, 0.8, 1.0]`, priority `0` work is shed once half of the burst is consumed,
+/// priority `1` past 80%, and priority `2` only by the rate limit itself.
+/// Priorities beyond the last threshold use the last one.
+#[derive(Clone, Debug)]
+pub struct Shedder {
+    thresholds: Vec<f64>,
+}
+
+impl Shedder {
+    /// # Panics
+    /// If `thresholds` is empty.
+    pub fn new(thresholds: impl Into<Vec<f64>>) -> Self {
+        let thresholds = thresholds.into();
+        assert!(!thresholds.is_empty(), "at least one threshold is required");
+
+        Self { thresholds }
+    }
+
+    /// Two priority classes, where priority `0` leaves the `reserved` share of the burst
+    /// to priority `1`, e.g. to keep admin and health check traffic flowing under load.
+    pub fn with_headroom(reserved: f64) -> Self {
+        Self::new([1.0 - reserved.clamp(0.0, 1.0), 1.0])
+    }
+
+    /// Pressure up to which work of `priority` is admitted.
+    pub fn threshold(&self, priority: usize) -> f64 {
+        let last = self.thresholds.len() - 1;
+        self.thresholds[priority.min(last)]
+    }
+
+    /// True if work of `priority` should be dropped under `pressure`.
+    pub fn should_shed(&self, pressure: f64, priority: usize) -> bool {
+        pressure > self.threshold(priority)
+    }
+
+    /// Admits work of `priority` if the pressure after booking `cost` stays within its
+    /// threshold and the quota allows it, updating `state` only then.
+    ///
+    /// # Returns
+    /// Shed work is [Error::DeniedUntil] the time pressure falls back to the threshold.
+    pub fn check_at(
+        &self,
+        state: &mut State,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+        priority: usize,
+    ) -> Result<(), Error> {
+        let mut next = state.clone();
+        next.check_and_modify_at(rate_limit, arrived_at, cost)?;
+
+        let threshold = self.threshold(priority);
+        if next.pressure(rate_limit, arrived_at) > threshold {
+            if let Some(eases_at) = next.pressure_eases_at(rate_limit, threshold) {
+                return Err(Error::DeniedUntil(eases_at));
+            }
+        }
+
+        *state = next;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn shed_low_priority_first() {
+        let shedder = Shedder::new([0.5, 1.0]);
+        let quota = Quota::new(4, Duration::from_secs(4));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        assert!(shedder.check_at(&mut state, &quota, now, 2, 0).is_ok());
+        assert!(
+            matches!(
+                shedder.check_at(&mut state, &quota, now, 1, 0),
+                Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(1)
+            ),
+            "low priority is shed past half of the burst"
+        );
+        assert!(shedder.check_at(&mut state, &quota, now, 2, 1).is_ok());
+        assert!(shedder.check_at(&mut state, &quota, now, 1, 7).is_err());
+    }
+
+    #[test]
+    fn reserved_headroom() {
+        let shedder = Shedder::with_headroom(0.2);
+        let quota = Quota::new(10, Duration::from_secs(10));
+        let mut state = State::default();
+        let now = Instant::now();
+
+        assert!(shedder.check_at(&mut state, &quota, now, 8, 0).is_ok());
+        assert!(shedder.check_at(&mut state, &quota, now, 1, 0).is_err());
+        assert!(shedder.check_at(&mut state, &quota, now, 2, 1).is_ok());
+    }
+
+    #[test]
+    fn should_shed() {
+        let shedder = Shedder::new(vec![0.5, 0.8]);
+
+        assert!(!shedder.should_shed(0.5, 0));
+        assert!(shedder.should_shed(0.6, 0));
+        assert!(!shedder.should_shed(0.6, 1));
+        assert!(shedder.should_shed(0.9, 5));
+    }
+}
@@ -0,0 +1,179 @@
+//! Drives an algorithm with scripted arrivals on a virtual clock, so quota
+//! configurations can be validated before they reach production.
+//!
+//! ```
+//! use std::time::Duration;
+//! use gcra::{Quota, State};
+//! use gcra::sim::{bursts, simulate};
+//!
+//! let quota = Quota::new(10, Duration::from_secs(1)).with_burst(5);
+//! let report = simulate::<State>(&quota, bursts(20, Duration::from_secs(1), Duration::from_secs(60)), 1);
+//!
+//! assert_eq!(5, report.max_burst);
+//! assert!(report.long_run_rate().unwrap() <= 10.0);
+//! ```
+
+use std::time::Duration;
+
+use crate::{Clock, ManualClock, Quota, RateLimitAlgorithm};
+
+/// Conformance statistics of a simulation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Amount of checks
+    pub arrivals: u64,
+
+    /// Resources admitted
+    pub admitted: u64,
+
+    /// Resources denied
+    pub denied: u64,
+
+    /// Most resources admitted at a single instant
+    pub max_burst: u64,
+
+    /// Most resources admitted in any window as long as the quota's period
+    pub max_per_period: u64,
+
+    /// Time from the first to the last arrival
+    pub span: Duration,
+
+    /// Emission interval of the simulated quota
+    pub emission_interval: Duration,
+}
+
+impl Report {
+    /// Admitted resources per second over the whole simulation, counting the emission
+    /// interval the last admission takes up, or `None` if every arrival was at the
+    /// same instant.
+    pub fn long_run_rate(&self) -> Option<f64> {
+        if self.span.is_zero() {
+            return None;
+        }
+
+        let duration = self.span.saturating_add(self.emission_interval);
+        Some(self.admitted as f64 / duration.as_secs_f64())
+    }
+}
+
+/// One arrival every `interval`, for `duration`.
+pub fn constant(interval: Duration, duration: Duration) -> Vec<Duration> {
+    bursts(1, interval, duration)
+}
+
+/// `size` arrivals at once, every `every`, for `duration`.
+pub fn bursts(size: u32, every: Duration, duration: Duration) -> Vec<Duration> {
+    assert!(!every.is_zero(), "arrivals must be spaced out");
+
+    let mut arrivals = Vec::new();
+    let mut offset = Duration::ZERO;
+    while offset < duration {
+        arrivals.extend(std::iter::repeat_n(offset, size as usize));
+        offset += every;
+    }
+
+    arrivals
+}
+
+/// Checks `cost` resources at every arrival, given as offsets from the start of the
+/// simulation in ascending order, against a fresh `A`.
+pub fn simulate<A>(
+    rate_limit: &Quota,
+    arrivals: impl IntoIterator<Item = Duration>,
+    cost: u32,
+) -> Report
+where
+    A: RateLimitAlgorithm + Default,
+{
+    let clock = ManualClock::default();
+    let mut algorithm = A::default();
+    let mut report = Report {
+        emission_interval: rate_limit.emission_interval,
+        ..Report::default()
+    };
+
+    // offsets of the admitted checks
+    let mut admitted: Vec<Duration> = Vec::new();
+    let mut first = None;
+    for offset in arrivals {
+        clock.set_elapsed(offset);
+        report.arrivals += 1;
+        report.span = offset - *first.get_or_insert(offset);
+
+        match algorithm.check_and_modify_at(rate_limit, clock.now(), cost) {
+            Ok(()) => {
+                report.admitted += cost as u64;
+                admitted.push(offset);
+            }
+            Err(_) => report.denied += cost as u64,
+        }
+    }
+
+    let cost = cost as u64;
+    let mut start = 0;
+    let mut same_instant = 0;
+    for (end, offset) in admitted.iter().enumerate() {
+        same_instant = match end {
+            0 => 1,
+            _ if admitted[end - 1] == *offset => same_instant + 1,
+            _ => 1,
+        };
+        report.max_burst = report.max_burst.max(same_instant * cost);
+
+        while start < end && *offset >= admitted[start] + rate_limit.period {
+            start += 1;
+        }
+        report.max_per_period = report.max_per_period.max((end - start + 1) as u64 * cost);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedWindow, State};
+
+    #[test]
+    fn gcra_conforms() {
+        let quota = Quota::new(10, Duration::from_secs(1));
+        let arrivals = constant(Duration::from_millis(10), Duration::from_secs(10));
+        let report = simulate::<State>(&quota, arrivals, 1);
+
+        assert_eq!(1000, report.arrivals);
+        assert!(report.max_per_period <= 20, "{:?}", report);
+        let rate = report.long_run_rate().unwrap();
+        assert!((rate - 10.0).abs() < 1.5, "{:?}", report);
+    }
+
+    #[test]
+    fn long_run_rate_counts_the_last_admission() {
+        let quota = Quota::new(1, Duration::from_secs(1));
+        let arrivals = [Duration::ZERO, Duration::ZERO, Duration::from_secs(1)];
+
+        let report = simulate::<State>(&quota, arrivals, 1);
+        assert_eq!(2, report.admitted);
+        assert_eq!(Some(1.0), report.long_run_rate());
+
+        let report = simulate::<State>(&quota, [Duration::ZERO; 3], 1);
+        assert_eq!(None, report.long_run_rate());
+    }
+
+    #[test]
+    fn fixed_window_boundary_burst() {
+        let quota = Quota::new(10, Duration::from_secs(1));
+
+        // the first arrival opens a window, which the two bursts straddle
+        let mut arrivals = vec![Duration::ZERO];
+        arrivals.extend([Duration::from_millis(999); 9]);
+        arrivals.extend([Duration::from_millis(1000); 10]);
+
+        let report = simulate::<FixedWindow>(&quota, arrivals, 1);
+        assert_eq!(20, report.admitted);
+        assert_eq!(
+            19, report.max_per_period,
+            "almost twice the limit around a window boundary"
+        );
+        assert_eq!(10, report.max_burst);
+    }
+}
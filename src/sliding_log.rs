@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::{Error, Quota, RateLimitAlgorithm};
+
+/// A sliding-window log, allowing no more than `resource_limit` resources in any
+/// trailing `period`, exactly.
+///
+/// GCRA only approximates that guarantee, in exchange for constant memory. This keeps one
+/// entry per admitted check still inside the window, so memory grows with the limit.
+#[derive(Clone, Debug, Default)]
+pub struct SlidingLog {
+    /// Arrival time and cost of the admitted checks, oldest first.
+    log: VecDeque<(Instant, u32)>,
+
+    /// Sum of the costs in `log`.
+    consumed: u32,
+}
+
+impl SlidingLog {
+    /// Drops the entries which are no longer in the window ending at `now`.
+    fn evict(&mut self, rate_limit: &Quota, now: Instant) {
+        while let Some(&(arrived_at, cost)) = self.log.front() {
+            if !leaves_by(arrived_at, rate_limit, now) {
+                break;
+            }
+
+            self.log.pop_front();
+            self.consumed -= cost;
+        }
+    }
+}
+
+/// True if an entry logged at `arrived_at` left the window by `now`, never for windows
+/// too long to end.
+fn leaves_by(arrived_at: Instant, rate_limit: &Quota, now: Instant) -> bool {
+    arrived_at
+        .checked_add(rate_limit.period)
+        .is_some_and(|left_at| left_at <= now)
+}
+
+impl RateLimitAlgorithm for SlidingLog {
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        // probes check a single resource
+        if cost.max(1) > rate_limit.resource_limit {
+            return Err(Error::DeniedIndefinitely(cost));
+        }
+
+        self.evict(rate_limit, arrived_at);
+
+        // The log may hold more than the limit, when the quota was lowered since
+        let needed = cost.max(1);
+        let available = rate_limit.resource_limit.saturating_sub(self.consumed);
+        if needed > available {
+            // Denied, must wait until enough of the oldest entries leave the window, at
+            // the latest once the log is empty since the cost is within the limit
+            let mut consumed = self.consumed;
+            let mut next = Some(arrived_at);
+            for &(logged_at, logged_cost) in &self.log {
+                consumed -= logged_cost;
+                next = logged_at.checked_add(rate_limit.period);
+                if consumed
+                    .checked_add(needed)
+                    .is_some_and(|consumed| consumed <= rate_limit.resource_limit)
+                {
+                    break;
+                }
+            }
+
+            return Err(next.map_or(Error::TimeOverflow, Error::DeniedUntil));
+        }
+
+        // probes leave the log as it was
+        if cost > 0 {
+            self.log.push_back((arrived_at, cost));
+            self.consumed += cost;
+        }
+        Ok(())
+    }
+
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        self.evict(rate_limit, arrived_at);
+
+        // Give back the most recent consumption first
+        let mut remaining = cost;
+        while remaining > 0 {
+            let Some((_, logged_cost)) = self.log.back_mut() else {
+                break;
+            };
+
+            let refund = remaining.min(*logged_cost);
+            *logged_cost -= refund;
+            self.consumed -= refund;
+            remaining -= refund;
+
+            if *logged_cost == 0 {
+                self.log.pop_back();
+            }
+        }
+    }
+
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        let consumed: u32 = self
+            .log
+            .iter()
+            .filter(|(arrived_at, _)| !leaves_by(*arrived_at, rate_limit, now))
+            .map(|(_, cost)| cost)
+            .sum();
+
+        rate_limit.resource_limit.saturating_sub(consumed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn sliding_log_exact_window() {
+        let rate_limit = Quota::new(3, Duration::from_secs(1));
+        let mut log = SlidingLog::default();
+        let now = Instant::now();
+
+        assert!(log.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(log
+            .check_and_modify_at(&rate_limit, now + Duration::from_millis(400), 2)
+            .is_ok());
+
+        // Only the first entry leaves the window at now + 1s
+        let denied = log.check_and_modify_at(&rate_limit, now + Duration::from_millis(900), 2);
+        assert!(
+            matches!(denied, Err(Error::DeniedUntil(next)) if next == now + Duration::from_millis(1400)),
+            "both entries must leave the window, got {:?}",
+            denied
+        );
+
+        assert_eq!(
+            1,
+            log.remaining_resources(&rate_limit, now + Duration::from_secs(1))
+        );
+        assert!(log
+            .check_and_modify_at(&rate_limit, now + Duration::from_secs(1), 1)
+            .is_ok());
+        assert!(log
+            .check_and_modify_at(&rate_limit, now + Duration::from_secs(1), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn sliding_log_revert() {
+        let rate_limit = Quota::new(3, Duration::from_secs(1));
+        let mut log = SlidingLog::default();
+        let now = Instant::now();
+
+        assert!(log.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(log.check_and_modify_at(&rate_limit, now, 2).is_ok());
+        log.revert_at(&rate_limit, now, 5);
+
+        assert_eq!(3, log.remaining_resources(&rate_limit, now));
+        assert!(log.log.is_empty(), "reverted entries should be dropped");
+    }
+
+    #[test]
+    fn sliding_log_lowered_quota() {
+        let mut log = SlidingLog::default();
+        let now = Instant::now();
+
+        for i in 0..8 {
+            let at = now + Duration::from_millis(100 * i);
+            assert!(log
+                .check_and_modify_at(&Quota::new(10, Duration::from_secs(1)), at, 1)
+                .is_ok());
+        }
+
+        // 6 of the 8 entries must leave the window to fit 1 more within 3
+        let lowered = Quota::new(3, Duration::from_secs(1));
+        let later = now + Duration::from_millis(800);
+        assert!(matches!(
+            log.check_and_modify_at(&lowered, later, 1),
+            Err(Error::DeniedUntil(next)) if next == now + Duration::from_millis(1500)
+        ));
+        assert_eq!(0, log.remaining_resources(&lowered, later));
+    }
+
+    #[test]
+    fn sliding_log_overflows() {
+        let now = Instant::now();
+
+        let mut log = SlidingLog::default();
+        let rate_limit = Quota::new(u32::MAX, Duration::from_secs(60));
+        assert!(log.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(log
+            .check_and_modify_at(&rate_limit, now, u32::MAX - 1)
+            .is_ok());
+        let lowered = Quota::new(u32::MAX - 1, Duration::from_secs(60));
+        assert!(matches!(
+            log.check_and_modify_at(&lowered, now, 2),
+            Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(60)
+        ));
+
+        let mut log = SlidingLog::default();
+        let rate_limit = Quota::new(2, Duration::MAX);
+        assert!(log.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(log.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(matches!(
+            log.check_and_modify_at(&rate_limit, now, 1),
+            Err(Error::TimeOverflow)
+        ));
+        assert_eq!(0, log.remaining_resources(&rate_limit, now));
+    }
+
+    #[test]
+    fn sliding_log_probes() {
+        let rate_limit = Quota::new(2, Duration::from_secs(1));
+        let mut log = SlidingLog::default();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            assert!(log.check_and_modify_at(&rate_limit, now, 0).is_ok());
+        }
+        assert!(log.log.is_empty(), "probes must not be logged");
+
+        assert!(log.check_and_modify_at(&rate_limit, now, 2).is_ok());
+        assert!(matches!(
+            log.check_and_modify_at(&rate_limit, now, 0),
+            Err(Error::DeniedUntil(next)) if next == now + rate_limit.period
+        ));
+        assert!(matches!(
+            log.check_and_modify_at(&Quota::zero(), now, 0),
+            Err(Error::DeniedIndefinitely(0))
+        ));
+    }
+}
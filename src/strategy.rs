@@ -0,0 +1,100 @@
+//! [proptest] strategies generating quotas, costs and arrival schedules, to property
+//! test components built on this crate.
+//!
+//! ```
+//! use gcra::strategy::{arrivals, cost, quota};
+//! use gcra::State;
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     fn never_exceeds_the_burst(
+//!         (quota, cost, arrivals) in quota().prop_flat_map(|quota| {
+//!             (Just(quota), cost(&quota), arrivals(&quota, 1..50))
+//!         })
+//!     ) {
+//!         let start = std::time::Instant::now();
+//!         let mut state = State::default();
+//!         for offset in arrivals {
+//!             let _ = state.check_and_modify_at(&quota, start + offset, cost);
+//!             prop_assert!(state.remaining_resources(&quota, start + offset) <= quota.burst);
+//!         }
+//!     }
+//! }
+//! # never_exceeds_the_burst();
+//! ```
+
+use std::time::Duration;
+
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+
+use crate::Quota;
+
+/// Quotas of 1 to 1000 resources per 1ms to 1h, with a burst from 1 up to twice the
+/// limit. The zero and unlimited sentinels are left to `Just`.
+pub fn quota() -> impl Strategy<Value = Quota> {
+    (1u32..=1_000, 1u64..=3_600_000).prop_flat_map(|(resource_limit, period)| {
+        let quota = Quota::new(resource_limit, Duration::from_millis(period));
+        (1..=resource_limit * 2).prop_map(move |burst| quota.with_burst(burst))
+    })
+}
+
+/// Costs from 0, a probe, up to the burst of `rate_limit`, the most a check can pass.
+pub fn cost(rate_limit: &Quota) -> impl Strategy<Value = u32> {
+    0..=rate_limit.burst
+}
+
+/// Arrival schedules as offsets from their start in ascending order, like
+/// `sim::simulate` takes, with gaps of up to twice the emission interval of
+/// `rate_limit`, so they run both ahead of and behind its rate.
+pub fn arrivals(
+    rate_limit: &Quota,
+    len: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<Duration>> {
+    let max_gap = u64::try_from(rate_limit.emission_interval.as_nanos())
+        .unwrap_or(u64::MAX)
+        .saturating_mul(2);
+
+    vec(0..=max_gap, len).prop_map(|gaps| {
+        let mut offset = Duration::ZERO;
+        gaps.into_iter()
+            .map(|gap| {
+                offset = offset.saturating_add(Duration::from_nanos(gap));
+                offset
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn generates_valid_values(
+            (quota, cost, arrivals) in quota().prop_flat_map(|quota| {
+                (Just(quota), cost(&quota), arrivals(&quota, 0..20))
+            })
+        ) {
+            prop_assert!(!quota.is_zero() && !quota.is_unlimited());
+            prop_assert!(quota.burst > 0 && cost <= quota.burst);
+            prop_assert!(arrivals.windows(2).all(|pair| pair[0] <= pair[1]));
+            prop_assert!(arrivals.len() < 20);
+        }
+    }
+
+    #[test]
+    fn arrivals_saturate() {
+        let mut runner = TestRunner::default();
+        let schedule = arrivals(&Quota::new(1, Duration::MAX), 3)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        assert_eq!(3, schedule.len());
+        assert!(schedule.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
@@ -0,0 +1,136 @@
+//! Assertions for testing code built on top of this crate, driven by a [ManualClock].
+//!
+//! ```
+//! use std::time::Duration;
+//! use gcra::{Quota, State};
+//! use gcra::test_util::Scenario;
+//!
+//! Scenario::<State>::new(Quota::new(2, Duration::from_secs(1)))
+//!     .allows(2)
+//!     .denies()
+//!     .advance(Duration::from_millis(500))
+//!     .allows(1)
+//!     .remaining(0);
+//! ```
+
+use std::time::Duration;
+
+use crate::{Clock, ManualClock, Quota, RateLimitAlgorithm};
+
+/// Asserts that `n` checks of cost one are allowed in a row at the clock's time.
+///
+/// ```
+/// use std::time::Duration;
+/// use gcra::{assert_allows, assert_denies, ManualClock, Quota, State};
+///
+/// let quota = Quota::new(2, Duration::from_secs(1));
+/// let clock = ManualClock::default();
+/// let mut state = State::default();
+///
+/// assert_allows!(state, quota, clock, 2);
+/// assert_denies!(state, quota, clock);
+/// ```
+#[macro_export]
+macro_rules! assert_allows {
+    ($algorithm:expr, $quota:expr, $clock:expr, $n:expr) => {{
+        let now = $crate::Clock::now(&$clock);
+        for i in 0..$n {
+            if let Err(err) =
+                $crate::RateLimitAlgorithm::check_and_modify_at(&mut $algorithm, &$quota, now, 1)
+            {
+                panic!("check #{} should be allowed, but got: {}", i + 1, err);
+            }
+        }
+    }};
+}
+
+/// Asserts that a check of cost one is denied at the clock's time.
+#[macro_export]
+macro_rules! assert_denies {
+    ($algorithm:expr, $quota:expr, $clock:expr) => {{
+        let now = $crate::Clock::now(&$clock);
+        if $crate::RateLimitAlgorithm::check_and_modify_at(&mut $algorithm, &$quota, now, 1).is_ok()
+        {
+            panic!("check should be denied, but was allowed");
+        }
+    }};
+}
+
+/// A fresh algorithm checked against a quota on a [ManualClock], whose steps panic
+/// when their expectation isn't met.
+#[derive(Debug)]
+pub struct Scenario<A> {
+    quota: Quota,
+    clock: ManualClock,
+    algorithm: A,
+}
+
+impl<A: RateLimitAlgorithm + Default> Scenario<A> {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            clock: ManualClock::default(),
+            algorithm: A::default(),
+        }
+    }
+}
+
+impl<A: RateLimitAlgorithm> Scenario<A> {
+    /// Expects `n` checks of cost one to be allowed in a row.
+    #[track_caller]
+    pub fn allows(&mut self, n: u32) -> &mut Self {
+        assert_allows!(self.algorithm, self.quota, self.clock, n);
+        self
+    }
+
+    /// Expects a check of cost one to be denied.
+    #[track_caller]
+    pub fn denies(&mut self) -> &mut Self {
+        assert_denies!(self.algorithm, self.quota, self.clock);
+        self
+    }
+
+    /// Expects `n` resources to remain.
+    #[track_caller]
+    pub fn remaining(&mut self, n: u32) -> &mut Self {
+        let remaining = self
+            .algorithm
+            .remaining_resources(&self.quota, self.clock.now());
+        assert_eq!(n, remaining, "remaining resources");
+        self
+    }
+
+    pub fn advance(&mut self, duration: Duration) -> &mut Self {
+        self.clock.advance(duration);
+        self
+    }
+
+    pub fn clock(&self) -> &ManualClock {
+        &self.clock
+    }
+
+    pub fn algorithm(&self) -> &A {
+        &self.algorithm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedWindow;
+
+    #[test]
+    fn scenario_fixed_window() {
+        Scenario::<FixedWindow>::new(Quota::new(2, Duration::from_secs(1)))
+            .allows(2)
+            .denies()
+            .advance(Duration::from_secs(1))
+            .remaining(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "check #3 should be allowed")]
+    fn scenario_reports_failing_check() {
+        Scenario::<crate::State>::new(Quota::new(2, Duration::from_secs(1))).allows(3);
+    }
+}
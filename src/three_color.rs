@@ -0,0 +1,222 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, State};
+
+/// Classification of an arrival against a committed and a peak rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// Conforms to the committed rate.
+    Green,
+
+    /// Exceeds the committed rate, but conforms to the peak rate.
+    Yellow,
+
+    /// Exceeds the peak rate.
+    Red,
+}
+
+/// A committed and a peak rate, each with its own burst, as network policers and many
+/// API plans are specified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DualRate {
+    /// Committed information rate (CIR) and committed burst size (CBS).
+    pub committed: Quota,
+
+    /// Peak information rate (PIR) and peak burst size (PBS).
+    pub peak: Quota,
+}
+
+impl DualRate {
+    /// A [zero](Quota::zero) committed rate marks no arrival green, and an
+    /// [unlimited](Quota::unlimited) peak rate marks none red.
+    ///
+    /// # Panics
+    /// If the peak rate is lower than the committed rate.
+    pub fn new(committed: Quota, peak: Quota) -> Self {
+        // both sentinels have no emission interval, so they are ranked explicitly
+        let ordered = if committed.is_zero() || peak.is_unlimited() {
+            true
+        } else if peak.is_zero() || committed.is_unlimited() {
+            false
+        } else {
+            peak.emission_interval <= committed.emission_interval
+        };
+        assert!(
+            ordered,
+            "peak rate must not be lower than the committed rate"
+        );
+
+        Self { committed, peak }
+    }
+}
+
+/// Two rate three color marker, after [RFC 2698](https://www.rfc-editor.org/rfc/rfc2698),
+/// in color-blind mode.
+///
+/// Arrivals are marked instead of being allowed or denied, so traffic above the committed
+/// rate can be deprioritized rather than dropped.
+#[derive(Clone, Debug, Default)]
+pub struct TwoRateState {
+    /// Tracks the committed rate.
+    pub committed: State,
+
+    /// Tracks the peak rate.
+    pub peak: State,
+}
+
+impl TwoRateState {
+    /// Checks the arrival against both rates of `rate`, and updates the states it conforms to.
+    ///
+    /// # Returns
+    /// The [Color] of the arrival if it conforms to the peak rate, otherwise the
+    /// peak rate's [Error], leaving both states unchanged.
+    pub fn check_at(
+        &mut self,
+        rate: &DualRate,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<Color, Error> {
+        self.conform(&rate.committed, &rate.peak, arrived_at, cost)
+    }
+
+    /// Simply passes the current Instant to [`check_at()`](TwoRateState::check_at)
+    #[inline]
+    pub fn check(&mut self, rate: &DualRate, cost: u32) -> Result<Color, Error> {
+        self.check_at(rate, Instant::now(), cost)
+    }
+
+    /// Marks the arrival, and updates the states it conforms to.
+    ///
+    /// Red arrivals leave both states unchanged, yellow ones only consume from the peak rate,
+    /// and green ones from both.
+    pub fn mark_at(
+        &mut self,
+        committed: &Quota,
+        peak: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Color {
+        self.conform(committed, peak, arrived_at, cost)
+            .unwrap_or(Color::Red)
+    }
+
+    /// Simply passes the current Instant to [`mark_at()`](TwoRateState::mark_at)
+    #[inline]
+    pub fn mark(&mut self, committed: &Quota, peak: &Quota, cost: u32) -> Color {
+        self.mark_at(committed, peak, Instant::now(), cost)
+    }
+
+    fn conform(
+        &mut self,
+        committed: &Quota,
+        peak: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<Color, Error> {
+        let mut next_peak = self.peak.clone();
+        next_peak.check_and_modify_at(peak, arrived_at, cost)?;
+
+        let mut next_committed = self.committed.clone();
+        let color = match next_committed.check_and_modify_at(committed, arrived_at, cost) {
+            Ok(()) => {
+                self.committed = next_committed;
+                Color::Green
+            }
+            Err(_) => Color::Yellow,
+        };
+
+        self.peak = next_peak;
+        Ok(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn three_color_marking() {
+        let committed = Quota::new(2, Duration::from_secs(1));
+        let peak = Quota::new(4, Duration::from_secs(1));
+        let mut state = TwoRateState::default();
+        let now = Instant::now();
+
+        let colors: Vec<_> = (0..5)
+            .map(|_| state.mark_at(&committed, &peak, now, 1))
+            .collect();
+        assert_eq!(
+            vec![
+                Color::Green,
+                Color::Green,
+                Color::Yellow,
+                Color::Yellow,
+                Color::Red
+            ],
+            colors
+        );
+
+        // the committed rate frees up first
+        let later = now + Duration::from_millis(500);
+        assert_eq!(Color::Green, state.mark_at(&committed, &peak, later, 1));
+    }
+
+    #[test]
+    fn red_leaves_state_unchanged() {
+        let committed = Quota::new(1, Duration::from_secs(1));
+        let peak = Quota::new(2, Duration::from_secs(1));
+        let mut state = TwoRateState::default();
+        let now = Instant::now();
+
+        assert_eq!(Color::Red, state.mark_at(&committed, &peak, now, 3));
+        assert_eq!(None, state.committed.tat);
+        assert_eq!(None, state.peak.tat);
+    }
+
+    #[test]
+    fn dual_rate_check() {
+        let rate = DualRate::new(
+            Quota::new(1, Duration::from_secs(1)),
+            Quota::new(2, Duration::from_secs(1)),
+        );
+        let mut state = TwoRateState::default();
+        let now = Instant::now();
+
+        assert_eq!(Color::Green, state.check_at(&rate, now, 1).unwrap());
+        assert_eq!(Color::Yellow, state.check_at(&rate, now, 1).unwrap());
+        assert!(matches!(
+            state.check_at(&rate, now, 1),
+            Err(Error::DeniedUntil(next)) if next == now + rate.peak.emission_interval
+        ));
+    }
+
+    #[test]
+    fn dual_rate_sentinels() {
+        let quota = Quota::new(1, Duration::from_secs(1));
+        let mut state = TwoRateState::default();
+        let now = Instant::now();
+
+        let rate = DualRate::new(Quota::zero(), quota);
+        assert_eq!(Color::Yellow, state.check_at(&rate, now, 1).unwrap());
+
+        let rate = DualRate::new(quota, Quota::unlimited());
+        assert_eq!(Color::Green, state.check_at(&rate, now, 1).unwrap());
+        assert_eq!(Color::Yellow, state.check_at(&rate, now, 1).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "peak rate")]
+    fn dual_rate_zero_peak() {
+        DualRate::new(Quota::new(1, Duration::from_secs(1)), Quota::zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "peak rate")]
+    fn dual_rate_peak_below_committed() {
+        DualRate::new(
+            Quota::new(2, Duration::from_secs(1)),
+            Quota::new(1, Duration::from_secs(1)),
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use crate::Quota;
+
+/// Estimates the admitted rate with an exponentially weighted moving average, so it can
+/// be compared to the [Quota] it runs against.
+///
+/// Admissions weigh less the older they are, by a factor of `e` every `window`.
+#[derive(Clone, Debug)]
+pub struct ThroughputEstimator {
+    window: Duration,
+    /// Resources per second, as of `last`
+    rate: f64,
+    last: Option<Instant>,
+}
+
+impl ThroughputEstimator {
+    /// # Panics
+    /// If the window is zero.
+    pub fn new(window: Duration) -> Self {
+        assert!(!window.is_zero(), "window must be greater than zero");
+
+        Self {
+            window,
+            rate: 0.0,
+            last: None,
+        }
+    }
+
+    /// Records `cost` resources admitted at `now`.
+    pub fn record_at(&mut self, now: Instant, cost: u32) {
+        self.rate = self.rate_at(now) + cost as f64 / self.window.as_secs_f64();
+        self.last = Some(self.last.map_or(now, |last| last.max(now)));
+    }
+
+    /// Estimated admitted resources per second at `now`.
+    pub fn rate_at(&self, now: Instant) -> f64 {
+        let last = match self.last {
+            Some(last) => last,
+            None => return 0.0,
+        };
+
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        self.rate * (-elapsed / self.window.as_secs_f64()).exp()
+    }
+
+    /// Estimated rate at `now` as a share of the rate of `rate_limit`, where `1.0`
+    /// means running right at the limit.
+    pub fn utilization_at(&self, rate_limit: &Quota, now: Instant) -> f64 {
+        if rate_limit.is_zero() {
+            return 1.0;
+        }
+        if rate_limit.is_unlimited() {
+            return 0.0;
+        }
+
+        let limit = rate_limit.resource_limit as f64 / rate_limit.period.as_secs_f64();
+        self.rate_at(now) / limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converges_to_steady_rate() {
+        let mut estimator = ThroughputEstimator::new(Duration::from_secs(1));
+        let quota = Quota::new(20, Duration::from_secs(1));
+        let start = Instant::now();
+
+        // 10 per second, for 10 seconds
+        for i in 0..100 {
+            estimator.record_at(start + Duration::from_millis(i * 100), 1);
+        }
+
+        let now = start + Duration::from_millis(9900);
+        let rate = estimator.rate_at(now);
+        assert!(
+            (rate - 10.0).abs() < 1.0,
+            "rate {} should be about 10",
+            rate
+        );
+
+        let utilization = estimator.utilization_at(&quota, now);
+        assert!((utilization - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn zero_window() {
+        ThroughputEstimator::new(Duration::ZERO);
+    }
+
+    #[test]
+    fn decays_when_idle() {
+        let mut estimator = ThroughputEstimator::new(Duration::from_secs(1));
+        let now = Instant::now();
+        assert_eq!(0.0, estimator.rate_at(now));
+
+        estimator.record_at(now, 10);
+        assert_eq!(10.0, estimator.rate_at(now));
+        assert!(estimator.rate_at(now + Duration::from_secs(10)) < 0.001);
+    }
+}
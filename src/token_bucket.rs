@@ -0,0 +1,153 @@
+use std::time::Instant;
+
+use crate::{Error, Quota, RateLimitAlgorithm};
+
+/// A classic token bucket holding up to `burst` tokens, refilled with one token every
+/// `emission_interval`.
+///
+/// It admits the same traffic as GCRA for a given [Quota], but keeps the token count
+/// around so it can be inspected or topped up directly.
+#[derive(Clone, Debug, Default)]
+pub struct TokenBucket {
+    /// Tokens left as of `last_refill`.
+    pub tokens: u32,
+
+    /// When tokens were last added, unset for a new (full) bucket.
+    pub last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Adds the tokens emitted since the last refill, up to `burst`.
+    pub fn refill(&mut self, rate_limit: &Quota, now: Instant) {
+        let (tokens, last_refill) = self.refilled(rate_limit, now);
+        self.tokens = tokens;
+        self.last_refill = Some(last_refill);
+    }
+
+    /// Tokens available at `now`.
+    pub fn tokens_at(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        self.refilled(rate_limit, now).0
+    }
+
+    /// Adds `tokens` to the bucket, up to `burst`.
+    pub fn top_up(&mut self, rate_limit: &Quota, now: Instant, tokens: u32) {
+        self.refill(rate_limit, now);
+        self.tokens = self.tokens.saturating_add(tokens).min(rate_limit.burst);
+    }
+
+    /// Token count and refill time after refilling at `now`, carrying over the part of an
+    /// emission interval which hasn't produced a token yet.
+    fn refilled(&self, rate_limit: &Quota, now: Instant) -> (u32, Instant) {
+        let last_refill = match self.last_refill {
+            Some(last_refill) if !rate_limit.emission_interval.is_zero() => last_refill,
+            _ => return (rate_limit.burst, now),
+        };
+
+        let elapsed = now.saturating_duration_since(last_refill);
+        let emitted = elapsed.as_nanos() / rate_limit.emission_interval.as_nanos();
+        let tokens = self.tokens as u128 + emitted;
+        if tokens >= rate_limit.burst as u128 {
+            return (rate_limit.burst, now);
+        }
+
+        let last_refill = last_refill + rate_limit.emission_interval * emitted as u32;
+        (tokens as u32, last_refill)
+    }
+}
+
+impl RateLimitAlgorithm for TokenBucket {
+    fn check_and_modify_at(
+        &mut self,
+        rate_limit: &Quota,
+        arrived_at: Instant,
+        cost: u32,
+    ) -> Result<(), Error> {
+        if cost > rate_limit.burst {
+            return Err(Error::DeniedIndefinitely(cost));
+        }
+
+        let (tokens, last_refill) = self.refilled(rate_limit, arrived_at);
+        if tokens < cost {
+            // Denied, must wait for the missing tokens
+            let missing = cost - tokens;
+            return match rate_limit
+                .checked_increment_interval(missing)
+                .and_then(|interval| last_refill.checked_add(interval))
+            {
+                Some(next) => Err(Error::DeniedUntil(next)),
+                None => Err(Error::TimeOverflow),
+            };
+        }
+
+        self.tokens = tokens - cost;
+        self.last_refill = Some(last_refill);
+        Ok(())
+    }
+
+    fn revert_at(&mut self, rate_limit: &Quota, arrived_at: Instant, cost: u32) {
+        self.top_up(rate_limit, arrived_at, cost);
+    }
+
+    fn remaining_resources(&self, rate_limit: &Quota, now: Instant) -> u32 {
+        self.tokens_at(rate_limit, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn token_bucket_refill() {
+        let rate_limit = Quota::new(4, Duration::from_secs(4));
+        let mut bucket = TokenBucket::default();
+        let now = Instant::now();
+
+        assert_eq!(4, bucket.tokens_at(&rate_limit, now), "starts full");
+        assert!(bucket.check_and_modify_at(&rate_limit, now, 4).is_ok());
+        assert_eq!(0, bucket.tokens);
+
+        assert!(matches!(
+            bucket.check_and_modify_at(&rate_limit, now, 2),
+            Err(Error::DeniedUntil(next)) if next == now + Duration::from_secs(2)
+        ));
+
+        // partial intervals carry over
+        let later = now + Duration::from_millis(1500);
+        bucket.refill(&rate_limit, later);
+        assert_eq!(1, bucket.tokens);
+        assert_eq!(
+            2,
+            bucket.tokens_at(&rate_limit, now + Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn token_bucket_top_up() {
+        let rate_limit = Quota::new(4, Duration::from_secs(4));
+        let mut bucket = TokenBucket::default();
+        let now = Instant::now();
+
+        assert!(bucket.check_and_modify_at(&rate_limit, now, 3).is_ok());
+        bucket.top_up(&rate_limit, now, 2);
+        assert_eq!(3, bucket.tokens);
+
+        bucket.top_up(&rate_limit, now, 10);
+        assert_eq!(4, bucket.tokens, "top up is capped at burst");
+    }
+
+    #[test]
+    fn token_bucket_overflow() {
+        let rate_limit = Quota::new(1, Duration::MAX);
+        let mut bucket = TokenBucket::default();
+        let now = Instant::now();
+
+        assert!(bucket.check_and_modify_at(&rate_limit, now, 1).is_ok());
+        assert!(matches!(
+            bucket.check_and_modify_at(&rate_limit, now, 1),
+            Err(Error::TimeOverflow)
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Quota, State};
+
+/// A recorded check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event<K> {
+    /// Arrival time, relative to the recorder's epoch.
+    pub offset: Duration,
+    pub key: K,
+    pub cost: u32,
+    /// Whether the check was allowed.
+    pub allowed: bool,
+}
+
+/// Records checks into a ring buffer, keeping the most recent `capacity` of them.
+#[derive(Debug)]
+pub struct Recorder<K> {
+    epoch: Instant,
+    capacity: usize,
+    events: VecDeque<Event<K>>,
+}
+
+impl<K> Recorder<K> {
+    pub fn new(epoch: Instant, capacity: usize) -> Self {
+        Self {
+            epoch,
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records the `result` of checking `cost` resources for `key` at `arrived_at`.
+    pub fn record(&mut self, arrived_at: Instant, key: K, cost: u32, result: &Result<(), Error>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(Event {
+            offset: arrived_at.saturating_duration_since(self.epoch),
+            key,
+            cost,
+            allowed: result.is_ok(),
+        });
+    }
+
+    /// Recorded events, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &Event<K>> {
+        self.events.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Writes one line per event: offset in nanoseconds, key, cost and `allow` or `deny`,
+    /// separated by spaces.
+    pub fn write_to(&self, mut writer: impl Write) -> std::io::Result<()>
+    where
+        K: Display,
+    {
+        for event in &self.events {
+            writeln!(
+                writer,
+                "{} {} {} {}",
+                event.offset.as_nanos(),
+                event.key,
+                event.cost,
+                if event.allowed { "allow" } else { "deny" }
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Re-applies `events` against fresh states, one per key, checked with `rate_limit`.
+///
+/// # Returns
+/// The events whose decision differs from the recorded one, empty if the replay matches.
+pub fn replay<'a, K>(
+    rate_limit: &Quota,
+    events: impl IntoIterator<Item = &'a Event<K>>,
+) -> Vec<&'a Event<K>>
+where
+    K: Hash + Eq + 'a,
+{
+    let epoch = Instant::now();
+    let mut states: HashMap<&K, State> = HashMap::new();
+
+    events
+        .into_iter()
+        .filter(|event| {
+            let allowed = states
+                .entry(&event.key)
+                .or_default()
+                .check_and_modify_at(rate_limit, epoch + event.offset, event.cost)
+                .is_ok();
+
+            allowed != event.allowed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(rate_limit: &Quota) -> Recorder<&'static str> {
+        let epoch = Instant::now();
+        let mut recorder = Recorder::new(epoch, 8);
+        let mut states: HashMap<&str, State> = HashMap::new();
+
+        for (millis, key) in [(0, "a"), (0, "a"), (10, "b"), (500, "a"), (600, "a")] {
+            let arrived_at = epoch + Duration::from_millis(millis);
+            let result = states
+                .entry(key)
+                .or_default()
+                .check_and_modify_at(rate_limit, arrived_at, 1);
+            recorder.record(arrived_at, key, 1, &result);
+        }
+
+        recorder
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let rate_limit = Quota::new(2, Duration::from_secs(1));
+        let recorder = record(&rate_limit);
+
+        let allowed: Vec<_> = recorder.events().map(|event| event.allowed).collect();
+        assert_eq!(vec![true, true, true, true, false], allowed);
+        assert!(replay(&rate_limit, recorder.events()).is_empty());
+
+        let stricter = Quota::new(1, Duration::from_secs(1));
+        let diverged = replay(&stricter, recorder.events());
+        let offsets: Vec<_> = diverged.iter().map(|event| event.offset).collect();
+        assert_eq!(vec![Duration::ZERO, Duration::from_millis(500)], offsets);
+    }
+
+    #[test]
+    fn ring_buffer_and_writer() {
+        let epoch = Instant::now();
+        let mut recorder = Recorder::new(epoch, 2);
+        recorder.record(epoch, "a", 1, &Ok(()));
+        recorder.record(epoch, "b", 2, &Ok(()));
+        recorder.record(epoch, "c", 3, &Err(Error::DeniedIndefinitely(3)));
+
+        let keys: Vec<_> = recorder.events().map(|event| event.key).collect();
+        assert_eq!(vec!["b", "c"], keys, "oldest events are dropped");
+
+        let mut out = Vec::new();
+        recorder.clear();
+        recorder.record(epoch + Duration::from_nanos(42), "d", 1, &Ok(()));
+        recorder.record(
+            epoch + Duration::from_nanos(43),
+            "e",
+            4,
+            &Err(Error::DeniedIndefinitely(4)),
+        );
+        recorder.write_to(&mut out).unwrap();
+        assert_eq!(
+            "42 d 1 allow\n43 e 4 deny\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}